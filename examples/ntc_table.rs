@@ -0,0 +1,97 @@
+//! Generates an `ntc::NtcPoint` table for a thermistor not covered by the tables in `ntc`.
+//!
+//! ```text
+//! cargo run --example ntc_table -- beta <name> <ohms at 25 degC> <beta in kelvin>
+//! cargo run --example ntc_table -- steinhart-hart <name> <a> <b> <c>
+//! ```
+//!
+//! The table covers -40 degC to 125 degC in 5 degC steps, in the layout of the tables in `ntc`,
+//! and is printed to stdout for pasting into firmware.
+
+use std::{env, process::ExitCode};
+
+const ZERO_CELSIUS_KELVIN: f64 = 273.15;
+const FIRST_CENTI_CELSIUS: i32 = -4000;
+const LAST_CENTI_CELSIUS: i32 = 12500;
+const STEP_CENTI_CELSIUS: usize = 500;
+
+/// A thermistor model, giving resistance from temperature.
+enum Model {
+    /// `R = R25 * exp(B * (1/T - 1/T25))`.
+    Beta { ohms_at_25: f64, beta: f64 },
+    /// `1/T = A + B ln(R) + C ln(R)^3`, solved for `R`.
+    SteinhartHart { a: f64, b: f64, c: f64 },
+}
+
+impl Model {
+    fn ohms(&self, celsius: f64) -> f64 {
+        let kelvin = celsius + ZERO_CELSIUS_KELVIN;
+        match *self {
+            Model::Beta { ohms_at_25, beta } => {
+                ohms_at_25 * (beta * (1.0 / kelvin - 1.0 / (25.0 + ZERO_CELSIUS_KELVIN))).exp()
+            }
+            Model::SteinhartHart { a, b, c } => {
+                let y = (a - 1.0 / kelvin) / (2.0 * c);
+                let x = ((b / (3.0 * c)).powi(3) + y * y).sqrt();
+                ((x - y).cbrt() - (x + y).cbrt()).exp()
+            }
+        }
+    }
+}
+
+fn parse_model(args: &[String]) -> Result<Model, String> {
+    let numbers = args
+        .get(1..)
+        .unwrap_or_default()
+        .iter()
+        .map(|text| text.parse::<f64>().map_err(|err| format!("{text}: {err}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    match (args.first().map(String::as_str), numbers.as_slice()) {
+        (Some("beta"), &[ohms_at_25, beta]) => Ok(Model::Beta { ohms_at_25, beta }),
+        (Some("steinhart-hart"), &[a, b, c]) => Ok(Model::SteinhartHart { a, b, c }),
+        _ => Err(
+            "expected `beta <name> <ohms> <beta>` or `steinhart-hart <name> <a> <b> <c>`".into(),
+        ),
+    }
+}
+
+/// Formats ohms with `_` between thousands, as rustfmt leaves the tables in `ntc`.
+fn grouped(ohms: u64) -> String {
+    let digits: Vec<char> = ohms.to_string().chars().collect();
+    let groups: Vec<String> = digits
+        .rchunks(3)
+        .rev()
+        .map(|group| group.iter().collect())
+        .collect();
+    groups.join("_")
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("usage: ntc_table (beta|steinhart-hart) <name> <coefficients>");
+        return ExitCode::FAILURE;
+    }
+    let name = args.remove(1);
+    let model = match parse_model(&args) {
+        Ok(model) => model,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("pub const {name}: &[NtcPoint] = &[");
+    for centi_celsius in (FIRST_CENTI_CELSIUS..=LAST_CENTI_CELSIUS).step_by(STEP_CENTI_CELSIUS) {
+        let ohms = model.ohms(f64::from(centi_celsius) / 100.0).round();
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "Thermistor resistances are positive and far below u64::MAX."
+        )]
+        let ohms = ohms as u64;
+        println!("    NtcPoint::new({}, {centi_celsius}),", grouped(ohms));
+    }
+    println!("];");
+    ExitCode::SUCCESS
+}
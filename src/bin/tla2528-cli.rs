@@ -0,0 +1,49 @@
+//! Bring-up and diagnostics tool for a TLA2528 on a Linux I2C bus.
+//!
+//! ```text
+//! tla2528-cli (--bus /dev/i2c-1 | --sim) [--address 0x10] <command>
+//! ```
+//!
+//! Commands are `probe`, `dump`, `read <register>`, `write <register> <value>`, `calibrate`,
+//! `pin <channel> <mode> [high|low]` and `stream [count] [--osr <ratio>]`. Registers are given
+//! by datasheet name, such as `OSR_CFG`, or by address. `--sim` runs against
+//! `sim::SimulatedTla2528` instead of hardware. The commands are implemented in `tla2528::cli`.
+
+use std::{
+    env, fmt,
+    io::{self, Write as _},
+    process::ExitCode,
+};
+
+use linux_embedded_hal::I2cdev;
+use tla2528::cli::{parse_options, run, simulated_device, Target};
+
+/// Writes command output straight to stdout, so streamed samples appear as they are taken.
+struct Stdout(io::Stdout);
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut out = Stdout(io::stdout());
+    let result = parse_options(env::args().skip(1)).and_then(|options| match options.target {
+        Target::Bus(ref path) => {
+            let bus = I2cdev::new(path).map_err(|err| format!("cannot open {path}: {err}"))?;
+            run(bus, options.address, &options.command, &mut out)
+        }
+        Target::Simulated => {
+            let device = simulated_device(options.address);
+            run(device, options.address, &options.command, &mut out)
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
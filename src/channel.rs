@@ -1,8 +1,12 @@
 use core::convert::TryFrom;
 
+use bitflags::bitflags;
+
 use crate::error::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Channel {
     Channel0 = 0x00,
@@ -46,3 +50,71 @@ pub(crate) fn try_from_i2c_data<E>(value: u8) -> Result<Channel, Error<E>> {
         _ => Err(Error::InvalidChannelAddress),
     }
 }
+
+impl Channel {
+    /// All channels, in sequence order.
+    pub const ALL: [Channel; 8] = [
+        Channel::Channel0,
+        Channel::Channel1,
+        Channel::Channel2,
+        Channel::Channel3,
+        Channel::Channel4,
+        Channel::Channel5,
+        Channel::Channel6,
+        Channel::Channel7,
+    ];
+
+    /// Position of the channel in an acquired frame.
+    #[must_use]
+    pub fn index(self) -> usize {
+        usize::from(self as u8)
+    }
+}
+
+bitflags! {
+    /// A set of channels, with bit N standing for `ChannelN`.
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct ChannelFlags: u8 {
+        const CHANNEL0 = 0b_0000_0001;
+        const CHANNEL1 = 0b_0000_0010;
+        const CHANNEL2 = 0b_0000_0100;
+        const CHANNEL3 = 0b_0000_1000;
+        const CHANNEL4 = 0b_0001_0000;
+        const CHANNEL5 = 0b_0010_0000;
+        const CHANNEL6 = 0b_0100_0000;
+        const CHANNEL7 = 0b_1000_0000;
+    }
+}
+
+impl From<Channel> for ChannelFlags {
+    fn from(channel: Channel) -> Self {
+        ChannelFlags::from_bits_retain(1 << (channel as u8))
+    }
+}
+
+impl ChannelFlags {
+    /// Whether `channel` is in the set.
+    #[must_use]
+    pub fn has(self, channel: Channel) -> bool {
+        self.contains(ChannelFlags::from(channel))
+    }
+
+    /// The channels in the set, in sequence order.
+    pub fn channels(self) -> impl Iterator<Item = Channel> {
+        Channel::ALL
+            .into_iter()
+            .filter(move |&channel| self.has(channel))
+    }
+}
+
+#[cfg(feature = "defmt")]
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided methods of defmt::Format are internal to defmt."
+)]
+impl defmt::Format for ChannelFlags {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "ChannelFlags({=u8:#010b})", self.bits());
+    }
+}
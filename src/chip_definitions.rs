@@ -1,11 +1,19 @@
+use core::fmt;
+
 use bitflags::bitflags;
 
+/// I2C high-speed mode master code `0000_1xxx`, given as the 7-bit address that places it on
+/// the bus. No device acknowledges a master code, so a NACK is the expected response.
+pub(crate) const I2C_HIGH_SPEED_MASTER_CODE: u8 = 0b_0000_0100;
+
 #[allow(
     dead_code,
     reason = "Defines all options in the interface, even those that are unused."
 )]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
-pub(crate) enum OpCode {
+pub enum OpCode {
     SingleRegisterRead = 0b_0001_0000,
     SingleRegisterWrite = 0b_0000_1000,
     SetBit = 0b_0001_1000,
@@ -18,13 +26,44 @@ impl OpCode {
         self as u8
     }
 }
+impl TryFrom<u8> for OpCode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<OpCode, Self::Error> {
+        match value {
+            x if x == OpCode::SingleRegisterRead as u8 => Ok(OpCode::SingleRegisterRead),
+            x if x == OpCode::SingleRegisterWrite as u8 => Ok(OpCode::SingleRegisterWrite),
+            x if x == OpCode::SetBit as u8 => Ok(OpCode::SetBit),
+            x if x == OpCode::ClearBit as u8 => Ok(OpCode::ClearBit),
+            x if x == OpCode::ReadContinuousRegisters as u8 => Ok(OpCode::ReadContinuousRegisters),
+            x if x == OpCode::WriteContinuousRegisters as u8 => {
+                Ok(OpCode::WriteContinuousRegisters)
+            }
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            OpCode::SingleRegisterRead => "SingleRegisterRead",
+            OpCode::SingleRegisterWrite => "SingleRegisterWrite",
+            OpCode::SetBit => "SetBit",
+            OpCode::ClearBit => "ClearBit",
+            OpCode::ReadContinuousRegisters => "ReadContinuousRegisters",
+            OpCode::WriteContinuousRegisters => "WriteContinuousRegisters",
+        })
+    }
+}
 
 #[allow(
     dead_code,
     reason = "Defines all options in the interface, even those that are unused."
 )]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
-pub(crate) enum RegisterAddress {
+pub enum RegisterAddress {
     SystemStatus = 0x00,
     GeneralConfig = 0x01,
     DataConfig = 0x02,
@@ -40,13 +79,67 @@ pub(crate) enum RegisterAddress {
     AutoSequenceChannelSelect = 0x12,
 }
 impl RegisterAddress {
-    pub(crate) fn value(self) -> u8 {
+    /// The register address.
+    #[must_use]
+    pub fn value(self) -> u8 {
         self as u8
     }
+
+    /// The register name used in the datasheet.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            RegisterAddress::SystemStatus => "SYSTEM_STATUS",
+            RegisterAddress::GeneralConfig => "GENERAL_CFG",
+            RegisterAddress::DataConfig => "DATA_CFG",
+            RegisterAddress::OsrConfig => "OSR_CFG",
+            RegisterAddress::OpModeConfig => "OPMODE_CFG",
+            RegisterAddress::PinConfig => "PIN_CFG",
+            RegisterAddress::GpioConfig => "GPIO_CFG",
+            RegisterAddress::GpioDriveConfig => "GPO_DRIVE_CFG",
+            RegisterAddress::GpOutValue => "GPO_VALUE",
+            RegisterAddress::GpInValue => "GPI_VALUE",
+            RegisterAddress::SequenceConfig => "SEQUENCE_CFG",
+            RegisterAddress::ChannelSelect => "CHANNEL_SEL",
+            RegisterAddress::AutoSequenceChannelSelect => "AUTO_SEQ_CH_SEL",
+        }
+    }
+}
+impl TryFrom<u8> for RegisterAddress {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<RegisterAddress, Self::Error> {
+        match value {
+            x if x == RegisterAddress::SystemStatus as u8 => Ok(RegisterAddress::SystemStatus),
+            x if x == RegisterAddress::GeneralConfig as u8 => Ok(RegisterAddress::GeneralConfig),
+            x if x == RegisterAddress::DataConfig as u8 => Ok(RegisterAddress::DataConfig),
+            x if x == RegisterAddress::OsrConfig as u8 => Ok(RegisterAddress::OsrConfig),
+            x if x == RegisterAddress::OpModeConfig as u8 => Ok(RegisterAddress::OpModeConfig),
+            x if x == RegisterAddress::PinConfig as u8 => Ok(RegisterAddress::PinConfig),
+            x if x == RegisterAddress::GpioConfig as u8 => Ok(RegisterAddress::GpioConfig),
+            x if x == RegisterAddress::GpioDriveConfig as u8 => {
+                Ok(RegisterAddress::GpioDriveConfig)
+            }
+            x if x == RegisterAddress::GpOutValue as u8 => Ok(RegisterAddress::GpOutValue),
+            x if x == RegisterAddress::GpInValue as u8 => Ok(RegisterAddress::GpInValue),
+            x if x == RegisterAddress::SequenceConfig as u8 => Ok(RegisterAddress::SequenceConfig),
+            x if x == RegisterAddress::ChannelSelect as u8 => Ok(RegisterAddress::ChannelSelect),
+            x if x == RegisterAddress::AutoSequenceChannelSelect as u8 => {
+                Ok(RegisterAddress::AutoSequenceChannelSelect)
+            }
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for RegisterAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 bitflags! {
     #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SystemStatusFlags: u8 {
         const _RESERVED_07 = 0b_1000_0000;
         const SEQUENCER_IN_PROGRESS = 0b_0100_0000;
@@ -62,6 +155,7 @@ bitflags! {
 
 bitflags! {
     #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct GeneralConfigFlags: u8 {
         const _RESERVED_07 = 0b_1000_0000;
         const _RESERVED_06 = 0b_0100_0000;
@@ -79,7 +173,7 @@ bitflags! {
     dead_code,
     reason = "Defines all options in the interface, even those that are unused."
 )]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub(crate) enum DataConfig {
     NormalDataNoChannelID = 0b_0000_0000,
@@ -92,12 +186,45 @@ impl DataConfig {
         self as u8
     }
 }
+impl TryFrom<u8> for DataConfig {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<DataConfig, Self::Error> {
+        match value {
+            x if x == DataConfig::NormalDataNoChannelID as u8 => {
+                Ok(DataConfig::NormalDataNoChannelID)
+            }
+            x if x == DataConfig::NormalDataAddChannelID as u8 => {
+                Ok(DataConfig::NormalDataAddChannelID)
+            }
+            x if x == DataConfig::FixedDataNoChannelID as u8 => {
+                Ok(DataConfig::FixedDataNoChannelID)
+            }
+            x if x == DataConfig::FixedDataAddChannelID as u8 => {
+                Ok(DataConfig::FixedDataAddChannelID)
+            }
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for DataConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            DataConfig::NormalDataNoChannelID => "NormalDataNoChannelID",
+            DataConfig::NormalDataAddChannelID => "NormalDataAddChannelID",
+            DataConfig::FixedDataNoChannelID => "FixedDataNoChannelID",
+            DataConfig::FixedDataAddChannelID => "FixedDataAddChannelID",
+        })
+    }
+}
 
 #[allow(
     dead_code,
     reason = "Defines all options in the interface, even those that are unused."
 )]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 #[non_exhaustive]
 pub enum Oversampling {
@@ -110,10 +237,78 @@ pub enum Oversampling {
     Ratio64 = 0b_0000_0110,
     Ratio128 = 0b_0000_0111,
 }
+#[cfg(feature = "defmt")]
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided methods of defmt::Format are internal to defmt."
+)]
+impl defmt::Format for SystemStatusFlags {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "SystemStatusFlags({=u8:#010b})", self.bits());
+    }
+}
+
+#[cfg(feature = "defmt")]
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided methods of defmt::Format are internal to defmt."
+)]
+impl defmt::Format for GeneralConfigFlags {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "GeneralConfigFlags({=u8:#010b})", self.bits());
+    }
+}
+
 impl Oversampling {
     pub(crate) fn value(self) -> u8 {
         self as u8
     }
+
+    /// Resolution of the results produced with this ratio.
+    #[must_use]
+    pub fn resolution(self) -> Resolution {
+        match self {
+            Oversampling::Ratio0 => Resolution::Bits12,
+            Oversampling::Ratio2
+            | Oversampling::Ratio4
+            | Oversampling::Ratio8
+            | Oversampling::Ratio16
+            | Oversampling::Ratio32
+            | Oversampling::Ratio64
+            | Oversampling::Ratio128 => Resolution::Bits16,
+        }
+    }
+}
+impl TryFrom<u8> for Oversampling {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Oversampling, Self::Error> {
+        match value {
+            x if x == Oversampling::Ratio0 as u8 => Ok(Oversampling::Ratio0),
+            x if x == Oversampling::Ratio2 as u8 => Ok(Oversampling::Ratio2),
+            x if x == Oversampling::Ratio4 as u8 => Ok(Oversampling::Ratio4),
+            x if x == Oversampling::Ratio8 as u8 => Ok(Oversampling::Ratio8),
+            x if x == Oversampling::Ratio16 as u8 => Ok(Oversampling::Ratio16),
+            x if x == Oversampling::Ratio32 as u8 => Ok(Oversampling::Ratio32),
+            x if x == Oversampling::Ratio64 as u8 => Ok(Oversampling::Ratio64),
+            x if x == Oversampling::Ratio128 as u8 => Ok(Oversampling::Ratio128),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for Oversampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Oversampling::Ratio0 => "Ratio0",
+            Oversampling::Ratio2 => "Ratio2",
+            Oversampling::Ratio4 => "Ratio4",
+            Oversampling::Ratio8 => "Ratio8",
+            Oversampling::Ratio16 => "Ratio16",
+            Oversampling::Ratio32 => "Ratio32",
+            Oversampling::Ratio64 => "Ratio64",
+            Oversampling::Ratio128 => "Ratio128",
+        })
+    }
 }
 
 #[allow(
@@ -124,7 +319,9 @@ impl Oversampling {
     non_camel_case_types,
     reason = "Underscores create clarity in enum values."
 )]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 #[non_exhaustive]
 pub enum SamplingRate {
@@ -166,11 +363,155 @@ impl SamplingRate {
         self as u8
     }
 }
+impl TryFrom<u8> for SamplingRate {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<SamplingRate, Self::Error> {
+        match value {
+            x if x == SamplingRate::HighSpeedOscillator_1_000_xxx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_1_000_xxx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_666_7xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_666_7xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_500_xxx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_500_xxx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_333_3xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_333_3xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_250_xxx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_250_xxx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_166_7xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_166_7xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_125_xxx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_125_xxx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_83_xxx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_83_xxx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_62_5xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_62_5xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_41_7xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_41_7xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_31_3xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_31_3xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_20_8xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_20_8xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_15_6xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_15_6xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_10_4xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_10_4xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_7_8xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_7_8xx_Sps)
+            }
+            x if x == SamplingRate::HighSpeedOscillator_5_2xx_Sps as u8 => {
+                Ok(SamplingRate::HighSpeedOscillator_5_2xx_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_31_25x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_31_25x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_20_83x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_20_83x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_15_63x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_15_63x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_10_42x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_10_42x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_7_81x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_7_81x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_5_21x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_5_21x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_3_91x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_3_91x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_2_60x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_2_60x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_1_95x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_1_95x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_1_30x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_1_30x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_98x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_98x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_65x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_65x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_49x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_49x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_33x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_33x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_24x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_24x_Sps)
+            }
+            x if x == SamplingRate::LowSpeedOscillator_0_16x_Sps as u8 => {
+                Ok(SamplingRate::LowSpeedOscillator_0_16x_Sps)
+            }
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for SamplingRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            SamplingRate::HighSpeedOscillator_1_000_xxx_Sps => "HighSpeedOscillator_1_000_xxx_Sps",
+            SamplingRate::HighSpeedOscillator_666_7xx_Sps => "HighSpeedOscillator_666_7xx_Sps",
+            SamplingRate::HighSpeedOscillator_500_xxx_Sps => "HighSpeedOscillator_500_xxx_Sps",
+            SamplingRate::HighSpeedOscillator_333_3xx_Sps => "HighSpeedOscillator_333_3xx_Sps",
+            SamplingRate::HighSpeedOscillator_250_xxx_Sps => "HighSpeedOscillator_250_xxx_Sps",
+            SamplingRate::HighSpeedOscillator_166_7xx_Sps => "HighSpeedOscillator_166_7xx_Sps",
+            SamplingRate::HighSpeedOscillator_125_xxx_Sps => "HighSpeedOscillator_125_xxx_Sps",
+            SamplingRate::HighSpeedOscillator_83_xxx_Sps => "HighSpeedOscillator_83_xxx_Sps",
+            SamplingRate::HighSpeedOscillator_62_5xx_Sps => "HighSpeedOscillator_62_5xx_Sps",
+            SamplingRate::HighSpeedOscillator_41_7xx_Sps => "HighSpeedOscillator_41_7xx_Sps",
+            SamplingRate::HighSpeedOscillator_31_3xx_Sps => "HighSpeedOscillator_31_3xx_Sps",
+            SamplingRate::HighSpeedOscillator_20_8xx_Sps => "HighSpeedOscillator_20_8xx_Sps",
+            SamplingRate::HighSpeedOscillator_15_6xx_Sps => "HighSpeedOscillator_15_6xx_Sps",
+            SamplingRate::HighSpeedOscillator_10_4xx_Sps => "HighSpeedOscillator_10_4xx_Sps",
+            SamplingRate::HighSpeedOscillator_7_8xx_Sps => "HighSpeedOscillator_7_8xx_Sps",
+            SamplingRate::HighSpeedOscillator_5_2xx_Sps => "HighSpeedOscillator_5_2xx_Sps",
+            SamplingRate::LowSpeedOscillator_31_25x_Sps => "LowSpeedOscillator_31_25x_Sps",
+            SamplingRate::LowSpeedOscillator_20_83x_Sps => "LowSpeedOscillator_20_83x_Sps",
+            SamplingRate::LowSpeedOscillator_15_63x_Sps => "LowSpeedOscillator_15_63x_Sps",
+            SamplingRate::LowSpeedOscillator_10_42x_Sps => "LowSpeedOscillator_10_42x_Sps",
+            SamplingRate::LowSpeedOscillator_7_81x_Sps => "LowSpeedOscillator_7_81x_Sps",
+            SamplingRate::LowSpeedOscillator_5_21x_Sps => "LowSpeedOscillator_5_21x_Sps",
+            SamplingRate::LowSpeedOscillator_3_91x_Sps => "LowSpeedOscillator_3_91x_Sps",
+            SamplingRate::LowSpeedOscillator_2_60x_Sps => "LowSpeedOscillator_2_60x_Sps",
+            SamplingRate::LowSpeedOscillator_1_95x_Sps => "LowSpeedOscillator_1_95x_Sps",
+            SamplingRate::LowSpeedOscillator_1_30x_Sps => "LowSpeedOscillator_1_30x_Sps",
+            SamplingRate::LowSpeedOscillator_0_98x_Sps => "LowSpeedOscillator_0_98x_Sps",
+            SamplingRate::LowSpeedOscillator_0_65x_Sps => "LowSpeedOscillator_0_65x_Sps",
+            SamplingRate::LowSpeedOscillator_0_49x_Sps => "LowSpeedOscillator_0_49x_Sps",
+            SamplingRate::LowSpeedOscillator_0_33x_Sps => "LowSpeedOscillator_0_33x_Sps",
+            SamplingRate::LowSpeedOscillator_0_24x_Sps => "LowSpeedOscillator_0_24x_Sps",
+            SamplingRate::LowSpeedOscillator_0_16x_Sps => "LowSpeedOscillator_0_16x_Sps",
+        })
+    }
+}
 
 #[allow(
     dead_code,
     reason = "Defines all options in the interface, even those that are unused."
 )]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub(crate) enum SequenceConfig {
     Manual = 0b_0000_0000,
@@ -182,3 +523,55 @@ impl SequenceConfig {
         self as u8
     }
 }
+impl TryFrom<u8> for SequenceConfig {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<SequenceConfig, Self::Error> {
+        match value {
+            x if x == SequenceConfig::Manual as u8 => Ok(SequenceConfig::Manual),
+            x if x == SequenceConfig::StoppedAuto as u8 => Ok(SequenceConfig::StoppedAuto),
+            x if x == SequenceConfig::StartedAuto as u8 => Ok(SequenceConfig::StartedAuto),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for SequenceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            SequenceConfig::Manual => "Manual",
+            SequenceConfig::StoppedAuto => "StoppedAuto",
+            SequenceConfig::StartedAuto => "StartedAuto",
+        })
+    }
+}
+
+/// Function of a pin, set through `PIN_CFG`, `GPIO_CFG` and `GPO_DRIVE_CFG`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PinMode {
+    AnalogInput,
+    DigitalInput,
+    DigitalOutputOpenDrain,
+    DigitalOutputPushPull,
+}
+
+/// Width of conversion results: 12 bits, or 16 bits when oversampling is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolution {
+    Bits12,
+    Bits16,
+}
+
+impl Resolution {
+    /// Number of codes, the result a signal at the reference voltage would give.
+    #[must_use]
+    pub const fn full_scale(self) -> u32 {
+        match self {
+            Resolution::Bits12 => 1 << 12,
+            Resolution::Bits16 => 1 << 16,
+        }
+    }
+}
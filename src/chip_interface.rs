@@ -1,28 +1,47 @@
 use crate::{
-    channel::{try_from_i2c_data, Channel},
+    channel::{try_from_i2c_data, Channel, ChannelFlags},
     chip_definitions::{
-        DataConfig, GeneralConfigFlags, OpCode, Oversampling, RegisterAddress, SamplingRate,
+        DataConfig, GeneralConfigFlags, Oversampling, PinMode, RegisterAddress, SamplingRate,
         SequenceConfig, SystemStatusFlags,
     },
-    error::Error,
+    error::{Error, Operation},
+    transport::Transport,
 };
-use embedded_hal::i2c::{I2c, SevenBitAddress};
 
-pub(crate) struct ChipInterface<I2C> {
-    i2c: I2C,
-    address: u8,
+/// Pin configuration registers, saved while a pin is temporarily reconfigured.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PinConfigSnapshot {
+    pin: u8,
+    gpio: u8,
+    drive: u8,
+    output: u8,
 }
 
-impl<I2C> ChipInterface<I2C>
+/// Bit mask of the oversampling ratio in `OSR_CFG`.
+const OSR_MASK: u8 = 0b_0000_0111;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct ChipInterface<T> {
+    transport: T,
+    /// Whether oversampling is enabled, which selects 3-byte 16-bit conversion frames over
+    /// 2-byte 12-bit frames. Follows writes to `OSR_CFG` and is read back after bit operations
+    /// on it and when a brownout is reported; the device powers up with it disabled.
+    averaged: bool,
+}
+
+impl<T> ChipInterface<T>
 where
-    I2C: I2c<SevenBitAddress>,
-    I2C::Error: Into<Error<I2C::Error>>,
+    T: Transport,
 {
-    pub(crate) fn new(i2c: I2C, address: u8) -> Self {
-        ChipInterface { i2c, address }
+    pub(crate) fn new(transport: T) -> Self {
+        ChipInterface {
+            transport,
+            averaged: false,
+        }
     }
 
-    pub(crate) fn configure_all_pins_as_analog_inputs(&mut self) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn configure_all_pins_as_analog_inputs(&mut self) -> Result<(), Error<T::Error>> {
         // Turn off auto-sequence mode
         self.write_sequence_config(SequenceConfig::Manual)?;
 
@@ -35,41 +54,115 @@ where
         self.register_write(RegisterAddress::PinConfig, 0b_0000_0000)
     }
 
+    pub(crate) fn configure_pin(
+        &mut self,
+        channel: Channel,
+        mode: PinMode,
+    ) -> Result<(), Error<T::Error>> {
+        let mask = 1 << channel as u8;
+        // Drive settings are applied before the pin is switched to GPIO to avoid glitches
+        match mode {
+            PinMode::AnalogInput => {}
+            PinMode::DigitalInput => self.clear_bits(RegisterAddress::GpioConfig, mask)?,
+            PinMode::DigitalOutputOpenDrain => {
+                self.clear_bits(RegisterAddress::GpioDriveConfig, mask)?;
+                self.set_bits(RegisterAddress::GpioConfig, mask)?;
+            }
+            PinMode::DigitalOutputPushPull => {
+                self.set_bits(RegisterAddress::GpioDriveConfig, mask)?;
+                self.set_bits(RegisterAddress::GpioConfig, mask)?;
+            }
+        }
+        if mode == PinMode::AnalogInput {
+            self.clear_bits(RegisterAddress::PinConfig, mask)
+        } else {
+            self.set_bits(RegisterAddress::PinConfig, mask)
+        }
+    }
+
+    pub(crate) fn save_pin_config(&mut self) -> Result<PinConfigSnapshot, Error<T::Error>> {
+        Ok(PinConfigSnapshot {
+            pin: self.register_read(RegisterAddress::PinConfig)?,
+            gpio: self.register_read(RegisterAddress::GpioConfig)?,
+            drive: self.register_read(RegisterAddress::GpioDriveConfig)?,
+            output: self.register_read(RegisterAddress::GpOutValue)?,
+        })
+    }
+
+    pub(crate) fn restore_pin_config(
+        &mut self,
+        snapshot: PinConfigSnapshot,
+    ) -> Result<(), Error<T::Error>> {
+        // Output values and drive settings are restored before the pin functions
+        self.register_write(RegisterAddress::GpOutValue, snapshot.output)?;
+        self.register_write(RegisterAddress::GpioDriveConfig, snapshot.drive)?;
+        self.register_write(RegisterAddress::GpioConfig, snapshot.gpio)?;
+        self.register_write(RegisterAddress::PinConfig, snapshot.pin)
+    }
+
+    pub(crate) fn read_oversampling(&mut self) -> Result<Option<Oversampling>, Error<T::Error>> {
+        let bits = self.register_read(RegisterAddress::OsrConfig)? & OSR_MASK;
+        self.averaged = bits != 0;
+        Ok(Oversampling::try_from(bits).ok())
+    }
+
+    pub(crate) fn write_output(
+        &mut self,
+        channel: Channel,
+        high: bool,
+    ) -> Result<(), Error<T::Error>> {
+        let mask = 1 << channel as u8;
+        if high {
+            self.set_bits(RegisterAddress::GpOutValue, mask)
+        } else {
+            self.clear_bits(RegisterAddress::GpOutValue, mask)
+        }
+    }
+
+    pub(crate) fn read_input(&mut self, channel: Channel) -> Result<bool, Error<T::Error>> {
+        Ok(self.register_read(RegisterAddress::GpInValue)? & (1 << channel as u8) != 0)
+    }
+
     pub(crate) fn configure_oversampling(
         &mut self,
         ratio: Oversampling,
-    ) -> Result<(), Error<I2C::Error>> {
+    ) -> Result<(), Error<T::Error>> {
         self.register_write(RegisterAddress::OsrConfig, ratio.value())
     }
 
     pub(crate) fn configure_sampling_rate(
         &mut self,
         config: SamplingRate,
-    ) -> Result<(), Error<I2C::Error>> {
+    ) -> Result<(), Error<T::Error>> {
         self.register_write(RegisterAddress::OpModeConfig, config.value())
     }
 
-    pub(crate) fn set_channel(&mut self, channel: Channel) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn set_channel(&mut self, channel: Channel) -> Result<(), Error<T::Error>> {
         self.register_write(RegisterAddress::ChannelSelect, channel as u8)
     }
 
-    pub(crate) fn configure_auto_sequence_mode(&mut self) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn configure_auto_sequence_mode(&mut self) -> Result<(), Error<T::Error>> {
         self.write_data_config(DataConfig::NormalDataAddChannelID)?;
         self.write_sequence_config(SequenceConfig::StoppedAuto)?;
         self.register_write(RegisterAddress::AutoSequenceChannelSelect, 0b_1111_1111)
     }
 
-    pub(crate) fn configure_manual_mode(&mut self) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn configure_manual_mode(&mut self) -> Result<(), Error<T::Error>> {
         self.write_data_config(DataConfig::NormalDataAddChannelID)?;
         self.write_sequence_config(SequenceConfig::Manual)
     }
 
-    pub(crate) fn read_system_status(&mut self) -> Result<SystemStatusFlags, Error<I2C::Error>> {
+    pub(crate) fn read_system_status(&mut self) -> Result<SystemStatusFlags, Error<T::Error>> {
         let bits = self.register_read(RegisterAddress::SystemStatus)?;
-        Ok(SystemStatusFlags::from_bits_retain(bits))
+        let status = SystemStatusFlags::from_bits_retain(bits);
+        if status.contains(SystemStatusFlags::BROWNOUT_RESET) {
+            // A brownout resets OSR_CFG with every other register, changing the frame width
+            self.read_oversampling()?;
+        }
+        Ok(status)
     }
 
-    pub(crate) fn read_general_config(&mut self) -> Result<GeneralConfigFlags, Error<I2C::Error>> {
+    pub(crate) fn read_general_config(&mut self) -> Result<GeneralConfigFlags, Error<T::Error>> {
         let bits = self.register_read(RegisterAddress::GeneralConfig)?;
         Ok(GeneralConfigFlags::from_bits_retain(bits))
     }
@@ -77,89 +170,157 @@ where
     pub(crate) fn write_general_config(
         &mut self,
         config: GeneralConfigFlags,
-    ) -> Result<(), Error<I2C::Error>> {
-        self.register_write(RegisterAddress::DataConfig, config.bits())
+    ) -> Result<(), Error<T::Error>> {
+        self.register_write(RegisterAddress::GeneralConfig, config.bits())
     }
 
-    pub(crate) fn write_data_config(
-        &mut self,
-        config: DataConfig,
-    ) -> Result<(), Error<I2C::Error>> {
+    pub(crate) fn write_data_config(&mut self, config: DataConfig) -> Result<(), Error<T::Error>> {
         self.register_write(RegisterAddress::DataConfig, config.value())
     }
 
     pub(crate) fn write_sequence_config(
         &mut self,
         config: SequenceConfig,
-    ) -> Result<(), Error<I2C::Error>> {
+    ) -> Result<(), Error<T::Error>> {
         self.register_write(RegisterAddress::SequenceConfig, config.value())
     }
 
-    fn register_read(&mut self, r: RegisterAddress) -> Result<u8, Error<I2C::Error>> {
-        let mut incoming = [0_u8; 1];
-        let res = self.i2c.write_read(
-            self.address,
-            &[OpCode::SingleRegisterRead.value(), r.value()],
-            &mut incoming,
-        );
+    pub(crate) fn register_read(&mut self, r: RegisterAddress) -> Result<u8, Error<T::Error>> {
+        let value = self
+            .transport
+            .register_read(r)
+            .map_err(|err| Error::bus(Operation::RegisterRead(r), err))?;
 
-        Ok(res.map(|()| incoming[0])?)
+        #[cfg(feature = "defmt")]
+        defmt::trace!("TLA2528 read {} = {=u8:#04x}", r, value);
+        Ok(value)
     }
 
-    fn register_write(&mut self, r: RegisterAddress, val: u8) -> Result<(), Error<I2C::Error>> {
-        self.i2c.write(
-            self.address,
-            &[OpCode::SingleRegisterWrite.value(), r.value(), val],
-        )?;
+    pub(crate) fn register_write(
+        &mut self,
+        r: RegisterAddress,
+        val: u8,
+    ) -> Result<(), Error<T::Error>> {
+        #[cfg(feature = "defmt")]
+        defmt::trace!("TLA2528 write {} = {=u8:#04x}", r, val);
+        self.transport
+            .register_write(r, val)
+            .map_err(|err| Error::bus(Operation::RegisterWrite(r), err))?;
+        if r == RegisterAddress::OsrConfig {
+            self.averaged = val & OSR_MASK != 0;
+        }
         Ok(())
     }
 
-    pub(crate) fn data_read(&mut self) -> Result<[u16; 8], Error<I2C::Error>> {
+    fn set_bits(&mut self, r: RegisterAddress, mask: u8) -> Result<(), Error<T::Error>> {
+        #[cfg(feature = "defmt")]
+        defmt::trace!("TLA2528 set bits {} |= {=u8:#04x}", r, mask);
+        self.transport
+            .set_bits(r, mask)
+            .map_err(|err| Error::bus(Operation::RegisterWrite(r), err))?;
+        self.resync_oversampling(r)
+    }
+
+    fn clear_bits(&mut self, r: RegisterAddress, mask: u8) -> Result<(), Error<T::Error>> {
+        #[cfg(feature = "defmt")]
+        defmt::trace!("TLA2528 clear bits {} &= !{=u8:#04x}", r, mask);
+        self.transport
+            .clear_bits(r, mask)
+            .map_err(|err| Error::bus(Operation::RegisterWrite(r), err))?;
+        self.resync_oversampling(r)
+    }
+
+    /// Reads back `OSR_CFG` after a bit operation on it, whose result depends on the bits
+    /// already set.
+    fn resync_oversampling(&mut self, r: RegisterAddress) -> Result<(), Error<T::Error>> {
+        if r == RegisterAddress::OsrConfig {
+            self.read_oversampling()?;
+        }
+        Ok(())
+    }
+
+    /// Bytes per conversion frame: 16-bit data and an ID byte with oversampling, otherwise
+    /// 12-bit data with the ID in the low nibble.
+    fn frame_len(&self) -> usize {
+        if self.averaged {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Splits a frame into its channel ID and result, as 16-bit or 12-bit code.
+    fn decode_frame(frame: &[u8]) -> Result<(Channel, u16), Error<T::Error>> {
+        match *frame {
+            [high, low] => {
+                let channel =
+                    Channel::try_from(low & 0x0F).map_err(|()| Error::InvalidChannelAddress)?;
+                Ok((channel, u16::from_be_bytes([high, low]) >> 4_u32))
+            }
+            [high, low, id] => Ok((try_from_i2c_data(id)?, u16::from_be_bytes([high, low]))),
+            _ => Err(Error::DataItemsMisOrdered),
+        }
+    }
+
+    pub(crate) fn data_read(&mut self) -> Result<[u16; 8], Error<T::Error>> {
+        Ok(self
+            .data_read_sequence(ChannelFlags::all())?
+            .map(Option::unwrap_or_default))
+    }
+
+    pub(crate) fn set_auto_sequence_channels(
+        &mut self,
+        channels: ChannelFlags,
+    ) -> Result<(), Error<T::Error>> {
+        self.register_write(RegisterAddress::AutoSequenceChannelSelect, channels.bits())
+    }
+
+    /// Reads one frame per channel in `channels`, which the sequencer returns in ascending order.
+    pub(crate) fn data_read_sequence(
+        &mut self,
+        channels: ChannelFlags,
+    ) -> Result<[Option<u16>; 8], Error<T::Error>> {
         let mut data_buffer = [0_u8; (8 * 3)];
-        if let Err(err) = self.i2c.read(self.address, &mut data_buffer) {
-            return Err(Error::I2cError(err));
+        let frame_len = self.frame_len();
+        let frame_bytes = channels.bits().count_ones() as usize * frame_len;
+        let incoming = data_buffer.get_mut(..frame_bytes).unwrap_or_default();
+        if let Err(err) = self.transport.read_frames(incoming, frame_len) {
+            return Err(Error::bus(Operation::DataRead, err));
         }
+        #[cfg(feature = "defmt")]
+        defmt::trace!("TLA2528 data frame {=[u8]:02x}", incoming);
 
-        let mut out = [0_u16; 8];
-        data_buffer
-            .chunks_exact(3)
-            .zip(out.iter_mut())
-            .for_each(|(chunk, destination)| {
-                let mut buf = [0_u8; 2];
-
-                #[allow(
-                    clippy::indexing_slicing,
-                    reason = "Safe due to slice matching buf size"
-                )]
-                buf.copy_from_slice(&chunk[..2]);
-                *destination = u16::from_be_bytes(buf);
-            });
+        let mut out = [None; 8];
+        for (frame, expected) in incoming.chunks_exact(frame_len).zip(channels.channels()) {
+            let (channel, value) = Self::decode_frame(frame)?;
+            if channel != expected {
+                return Err(Error::DataItemsMisOrdered);
+            }
+            if let Some(destination) = out.get_mut(expected.index()) {
+                *destination = Some(value);
+            }
+        }
         Ok(out)
     }
 
     pub(crate) fn data_channel_read(
         &mut self,
         desired_channel: Channel,
-    ) -> Result<(u16, usize), Error<I2C::Error>> {
+    ) -> Result<(u16, usize), Error<T::Error>> {
         const MAX_CHANNEL_READ_TRIES: usize = 32;
+        let frame_len = self.frame_len();
         for i in 0..MAX_CHANNEL_READ_TRIES {
             let mut data_buffer = [0_u8; 3];
-            if let Err(err) = self.i2c.read(self.address, &mut data_buffer) {
-                return Err(Error::I2cError(err));
+            let incoming = data_buffer.get_mut(..frame_len).unwrap_or_default();
+            if let Err(err) = self.transport.read_frames(incoming, frame_len) {
+                return Err(Error::bus(Operation::DataRead, err));
             }
+            #[cfg(feature = "defmt")]
+            defmt::trace!("TLA2528 data {=[u8]:02x}", incoming);
 
-            let read_channel = try_from_i2c_data(data_buffer[2])?;
-
+            let (read_channel, value) = Self::decode_frame(incoming)?;
             if read_channel == desired_channel {
-                let mut buf = [0_u8; 2];
-
-                #[allow(
-                    clippy::indexing_slicing,
-                    reason = "Safe due to slice matching buf size"
-                )]
-                buf.copy_from_slice(&data_buffer[..2]);
-
-                return Ok((u16::from_be_bytes(buf), i + 1));
+                return Ok((value, i + 1));
             }
         }
         Err(Error::IncorrectChannelAddress)
@@ -0,0 +1,401 @@
+//! Commands of the `tla2528-cli` bring-up and diagnostics tool.
+//!
+//! The binary only opens the Linux I2C bus and prints to stdout; argument parsing and the
+//! commands live here, generic over the bus and the output, so they can be run against
+//! `sim::SimulatedTla2528`.
+
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::{error::Error as CoreError, fmt};
+
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+use crate::{
+    channel::Channel,
+    chip_definitions::{Oversampling, PinMode, RegisterAddress},
+    decoder::RegisterValue,
+    sim::SimulatedTla2528,
+    transport::I2cTransport,
+    Tla2528,
+};
+
+pub const DEFAULT_ADDRESS: u8 = 0x10;
+const DEFAULT_STREAM_COUNT: u32 = 100;
+const LAST_REGISTER: u8 = 0x12;
+
+pub const USAGE: &str = "\
+usage: tla2528-cli (--bus <device> | --sim) [--address <address>] <command>
+
+commands:
+  probe                              read SYSTEM_STATUS to check the device responds
+  dump                               print every register
+  read <register>                    print one register
+  write <register> <value>           write one register
+  calibrate                          run the ADC offset calibration
+  pin <channel> <mode> [high|low]    configure a pin: analog, input, open-drain, push-pull
+  stream [count] [--osr <ratio>]     print samples of all channels as CSV
+
+registers are given by datasheet name (OSR_CFG) or address (0x03)";
+
+pub type CliResult<T> = Result<T, Box<dyn CoreError>>;
+
+/// The device the tool talks to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A Linux I2C bus device, such as `/dev/i2c-1`.
+    Bus(String),
+    /// The simulated device from `simulated_device()`.
+    Simulated,
+}
+
+/// Parsed command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub target: Target,
+    pub address: u8,
+    pub command: Vec<String>,
+}
+
+/// Parses the arguments following the program name.
+///
+/// # Errors
+///
+/// Returns the usage text when no target or command is given, or for `--help`.
+pub fn parse_options<A>(mut args: A) -> CliResult<Options>
+where
+    A: Iterator<Item = String>,
+{
+    let mut target = None;
+    let mut address = DEFAULT_ADDRESS;
+    let mut command = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bus" => target = Some(Target::Bus(args.next().ok_or("--bus needs a device")?)),
+            "--sim" => target = Some(Target::Simulated),
+            "--address" => {
+                address = parse_number(&args.next().ok_or("--address needs a value")?)?;
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => command.push(arg),
+        }
+    }
+    let chosen = target.ok_or(USAGE)?;
+    if command.is_empty() {
+        return Err(USAGE.into());
+    }
+    Ok(Options {
+        target: chosen,
+        address,
+        command,
+    })
+}
+
+/// A simulated device with distinct levels per channel, which make the streamed columns easy
+/// to tell apart.
+#[must_use]
+pub fn simulated_device(address: u8) -> SimulatedTla2528<'static> {
+    let mut device = SimulatedTla2528::new(address);
+    for (channel, code) in Channel::ALL.into_iter().zip((0_u16..).step_by(512)) {
+        device.set_input(channel, code);
+    }
+    device
+}
+
+/// Runs one command against the device at `address`, writing its output to `out`.
+///
+/// # Errors
+///
+/// Returns the usage text for unknown commands, and passes out argument and bus errors.
+pub fn run<I2C, W>(i2c: I2C, address: u8, command: &[String], out: &mut W) -> CliResult<()>
+where
+    I2C: I2c<SevenBitAddress>,
+    I2C::Error: 'static,
+    W: fmt::Write,
+{
+    let mut adc = Tla2528::new(i2c, address);
+    let args: Vec<&str> = command.iter().map(String::as_str).collect();
+    match *args.as_slice() {
+        ["probe"] => {
+            let status = adc.get_system_status()?;
+            writeln!(out, "TLA2528 responded at {address:#04x}")?;
+            write_register(out, RegisterAddress::SystemStatus, status.bits())?;
+        }
+        ["dump"] => {
+            for register in registers() {
+                let value = adc.read_register(register)?;
+                write_register(out, register, value)?;
+            }
+        }
+        ["read", register_text] => {
+            let register = parse_register(register_text)?;
+            let value = adc.read_register(register)?;
+            write_register(out, register, value)?;
+        }
+        ["write", register_text, value] => {
+            let register = parse_register(register_text)?;
+            adc.write_register(register, parse_number(value)?)?;
+            let readback = adc.read_register(register)?;
+            write_register(out, register, readback)?;
+        }
+        ["calibrate"] => {
+            adc.calibrate()?;
+            writeln!(out, "calibration complete")?;
+        }
+        ["pin", channel_text, mode_text, ref level_args @ ..] => {
+            let channel = parse_channel(channel_text)?;
+            let mode = parse_pin_mode(mode_text)?;
+            let level = match (mode, level_args) {
+                (
+                    PinMode::DigitalOutputOpenDrain | PinMode::DigitalOutputPushPull,
+                    &[level_text],
+                ) => Some(parse_level(level_text)?),
+                (_, &[]) => None,
+                _ => return Err("a level can only be given for output pins".into()),
+            };
+            // The level is set before the pin becomes an output, so it does not glitch
+            if let Some(high) = level {
+                adc.set_output(channel, high)?;
+            }
+            adc.configure_pin(channel, mode)?;
+            if mode != PinMode::AnalogInput {
+                let high = adc.read_input(channel)?;
+                writeln!(
+                    out,
+                    "ch{} reads {}",
+                    channel.index(),
+                    if high { "high" } else { "low" }
+                )?;
+            }
+        }
+        ["stream", ref rest @ ..] => stream(&mut adc, rest, out)?,
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
+
+fn stream<I2C, W>(adc: &mut Tla2528<I2cTransport<I2C>>, args: &[&str], out: &mut W) -> CliResult<()>
+where
+    I2C: I2c<SevenBitAddress>,
+    I2C::Error: 'static,
+    W: fmt::Write,
+{
+    let mut count = DEFAULT_STREAM_COUNT;
+    let mut ratio = Oversampling::Ratio0;
+    let mut args_iter = args.iter();
+    while let Some(&arg) = args_iter.next() {
+        if arg == "--osr" {
+            ratio = parse_oversampling(args_iter.next().ok_or("--osr needs a ratio")?)?;
+        } else {
+            count = parse_number(arg)?;
+        }
+    }
+
+    adc.prepare_for_auto_sequence_mode()?;
+    adc.set_oversampling_ratio(ratio)?;
+    writeln!(out, "sample,ch0,ch1,ch2,ch3,ch4,ch5,ch6,ch7")?;
+    for sample in 0..count {
+        write!(out, "{sample}")?;
+        for value in adc.acquire_data()? {
+            write!(out, ",{value}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn registers() -> impl Iterator<Item = RegisterAddress> {
+    (0..=LAST_REGISTER).filter_map(|address| RegisterAddress::try_from(address).ok())
+}
+
+fn write_register<W>(out: &mut W, register: RegisterAddress, value: u8) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    let register_value = RegisterValue {
+        register: register.value(),
+        value,
+    };
+    writeln!(
+        out,
+        "{:#04x} {register_value}   ({value:#04x})",
+        register_value.register
+    )
+}
+
+fn parse_number<T>(text: &str) -> CliResult<T>
+where
+    T: TryFrom<u32>,
+{
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|err| format!("invalid number {text}: {err}"))?;
+    T::try_from(value).map_err(|_err| format!("{text} is out of range").into())
+}
+
+fn parse_register(text: &str) -> CliResult<RegisterAddress> {
+    if let Some(register) = registers().find(|register| register.name().eq_ignore_ascii_case(text))
+    {
+        return Ok(register);
+    }
+    let address: u8 = parse_number(text)?;
+    RegisterAddress::try_from(address).map_err(|()| format!("unknown register {text}").into())
+}
+
+fn parse_channel(text: &str) -> CliResult<Channel> {
+    let index: u8 = parse_number(text.trim_start_matches("ch"))?;
+    Channel::try_from(index).map_err(|()| format!("unknown channel {text}").into())
+}
+
+fn parse_pin_mode(text: &str) -> CliResult<PinMode> {
+    match text {
+        "analog" => Ok(PinMode::AnalogInput),
+        "input" => Ok(PinMode::DigitalInput),
+        "open-drain" => Ok(PinMode::DigitalOutputOpenDrain),
+        "push-pull" => Ok(PinMode::DigitalOutputPushPull),
+        _ => Err(format!("unknown pin mode {text}").into()),
+    }
+}
+
+fn parse_level(text: &str) -> CliResult<bool> {
+    match text {
+        "high" | "1" => Ok(true),
+        "low" | "0" => Ok(false),
+        _ => Err(format!("unknown level {text}").into()),
+    }
+}
+
+fn parse_oversampling(text: &str) -> CliResult<Oversampling> {
+    let ratio = match parse_number::<u32>(text)? {
+        0 | 1 => Oversampling::Ratio0,
+        2 => Oversampling::Ratio2,
+        4 => Oversampling::Ratio4,
+        8 => Oversampling::Ratio8,
+        16 => Oversampling::Ratio16,
+        32 => Oversampling::Ratio32,
+        64 => Oversampling::Ratio64,
+        128 => Oversampling::Ratio128,
+        _ => return Err(format!("unsupported oversampling ratio {text}").into()),
+    };
+    Ok(ratio)
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::{parse_options, run, simulated_device, Options, Target, DEFAULT_ADDRESS};
+    use crate::{channel::Channel, chip_definitions::RegisterAddress};
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_owned).collect()
+    }
+
+    fn run_simulated(command: &str) -> String {
+        let mut out = String::new();
+        run(
+            simulated_device(DEFAULT_ADDRESS),
+            DEFAULT_ADDRESS,
+            &words(command),
+            &mut out,
+        )
+        .unwrap();
+        out
+    }
+
+    #[test]
+    fn parses_target_address_and_command() {
+        let options =
+            parse_options(words("--bus /dev/i2c-1 --address 0x13 read OSR_CFG").into_iter())
+                .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                target: Target::Bus("/dev/i2c-1".to_owned()),
+                address: 0x13,
+                command: words("read OSR_CFG"),
+            }
+        );
+        assert!(parse_options(words("--sim").into_iter()).is_err());
+        assert!(parse_options(words("probe").into_iter()).is_err());
+    }
+
+    #[test]
+    fn probes_and_dumps() {
+        let probe = run_simulated("probe");
+        assert!(probe.starts_with("TLA2528 responded at 0x10\n0x00 SYSTEM_STATUS"));
+        assert_eq!(run_simulated("dump").lines().count(), 13);
+    }
+
+    #[test]
+    fn writes_and_reads_back_registers() {
+        assert_eq!(
+            run_simulated("write OSR_CFG 0x04"),
+            "0x03 OSR_CFG = Ratio16   (0x04)\n"
+        );
+        assert_eq!(
+            run_simulated("read 0x03"),
+            "0x03 OSR_CFG = Ratio0   (0x00)\n"
+        );
+        let mut out = String::new();
+        assert!(run(
+            simulated_device(DEFAULT_ADDRESS),
+            DEFAULT_ADDRESS,
+            &words("read NO_SUCH_REGISTER"),
+            &mut out
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn calibrates() {
+        assert_eq!(run_simulated("calibrate"), "calibration complete\n");
+    }
+
+    #[test]
+    fn configures_pins() {
+        let mut sim = simulated_device(DEFAULT_ADDRESS);
+        let mut out = String::new();
+        run(
+            &mut sim,
+            DEFAULT_ADDRESS,
+            &words("pin ch2 push-pull high"),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, "ch2 reads high\n");
+        assert_eq!(sim.output_level(Channel::Channel2), Some(true));
+
+        // Rejected arguments leave the pins as they were
+        for rejected in [
+            "pin 3 input high",
+            "pin 3 push-pull maybe",
+            "pin 3 analog low",
+        ] {
+            assert!(run(&mut sim, DEFAULT_ADDRESS, &words(rejected), &mut out).is_err());
+        }
+        assert_eq!(sim.register(RegisterAddress::PinConfig), 0b_0000_0100);
+        assert_eq!(sim.register(RegisterAddress::GpioConfig), 0b_0000_0100);
+        assert_eq!(sim.register(RegisterAddress::GpOutValue), 0b_0000_0100);
+    }
+
+    #[test]
+    fn streams_without_and_with_oversampling() {
+        assert_eq!(
+            run_simulated("stream 2"),
+            "sample,ch0,ch1,ch2,ch3,ch4,ch5,ch6,ch7\n\
+             0,0,512,1024,1536,2048,2560,3072,3584\n\
+             1,0,512,1024,1536,2048,2560,3072,3584\n"
+        );
+        assert_eq!(
+            run_simulated("stream 1 --osr 16"),
+            "sample,ch0,ch1,ch2,ch3,ch4,ch5,ch6,ch7\n\
+             0,0,8192,16384,24576,32768,40960,49152,57344\n"
+        );
+    }
+}
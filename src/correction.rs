@@ -0,0 +1,316 @@
+//! Per-channel gain and offset correction from two-point system calibration.
+//!
+//! `Tla2528::calibrate()` only removes the ADC's internal offset. A `CorrectionTable` also
+//! corrects the gain and offset of the signal chain in front of each channel, from results
+//! measured while known references are applied. Corrections are fixed point: the gain is a
+//! Q16.16 factor and the offset is in 1/256ths of a code.
+//!
+//! The table can be stored in flash with serde, or as the fixed `CorrectionTable::BYTES` byte
+//! layout from `to_bytes()`.
+
+use crate::{channel::Channel, filter::FrameProcessor};
+
+/// Fractional bits of `Correction::gain`.
+pub const GAIN_FRACTION_BITS: u32 = 16;
+
+/// A gain of 1.
+pub const UNITY_GAIN: i32 = 1 << GAIN_FRACTION_BITS;
+
+/// Fractional bits of `Correction::offset`.
+pub const OFFSET_FRACTION_BITS: u32 = 8;
+
+/// A result measured while a known reference was applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReferencePoint {
+    /// The result read from the channel.
+    pub measured: u16,
+    /// The result an ideal signal chain would give for the reference.
+    pub expected: u16,
+}
+
+/// Gain and offset correction for one channel, `corrected = raw * gain / 2^16 + offset / 2^8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Correction {
+    /// Q16.16 gain factor.
+    pub gain: i32,
+    /// Q24.8 offset in codes, added after the gain.
+    pub offset: i32,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Correction::IDENTITY
+    }
+}
+
+impl Correction {
+    /// A correction that leaves results unchanged.
+    pub const IDENTITY: Correction = Correction {
+        gain: UNITY_GAIN,
+        offset: 0,
+    };
+
+    /// Size of the `to_bytes()` form.
+    pub const BYTES: usize = 8;
+
+    /// Computes the correction that maps both measured results onto their expected values.
+    ///
+    /// Returns `None` if the measured results are equal or the gain does not fit in Q16.16.
+    #[must_use]
+    pub fn from_two_points(first: ReferencePoint, second: ReferencePoint) -> Option<Self> {
+        let measured_span = i64::from(second.measured) - i64::from(first.measured);
+        let expected_span = i64::from(second.expected) - i64::from(first.expected);
+        if measured_span == 0 {
+            return None;
+        }
+        let gain = i32::try_from(rounded_div(
+            expected_span << GAIN_FRACTION_BITS,
+            measured_span,
+        ))
+        .ok()?;
+        let scaled = i64::from(first.measured) * i64::from(gain);
+        let offset = rounded_div(
+            (i64::from(first.expected) << GAIN_FRACTION_BITS) - scaled,
+            1 << (GAIN_FRACTION_BITS - OFFSET_FRACTION_BITS),
+        );
+        Some(Correction {
+            gain,
+            offset: i32::try_from(offset).ok()?,
+        })
+    }
+
+    /// Applies the correction, saturating at the limits of a `u16` result.
+    #[must_use]
+    pub fn apply(&self, raw: u16) -> u16 {
+        let offset = i64::from(self.offset) << (GAIN_FRACTION_BITS - OFFSET_FRACTION_BITS);
+        let scaled = i64::from(raw) * i64::from(self.gain) + offset;
+        let corrected = rounded_div(scaled, 1 << GAIN_FRACTION_BITS).clamp(0, i64::from(u16::MAX));
+        u16::try_from(corrected).unwrap_or(u16::MAX)
+    }
+
+    /// Little-endian gain followed by little-endian offset.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; Correction::BYTES] {
+        let mut bytes = [0_u8; Correction::BYTES];
+        let (gain, offset) = bytes.split_at_mut(4);
+        gain.copy_from_slice(&self.gain.to_le_bytes());
+        offset.copy_from_slice(&self.offset.to_le_bytes());
+        bytes
+    }
+
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8; Correction::BYTES]) -> Self {
+        let [g0, g1, g2, g3, o0, o1, o2, o3] = *bytes;
+        Correction {
+            gain: i32::from_le_bytes([g0, g1, g2, g3]),
+            offset: i32::from_le_bytes([o0, o1, o2, o3]),
+        }
+    }
+}
+
+/// Divides rounding half away from zero. `divisor` must not be zero.
+fn rounded_div(dividend: i64, divisor: i64) -> i64 {
+    let half = divisor.abs() / 2;
+    if (dividend < 0) == (divisor < 0) {
+        (dividend.abs() + half) / divisor.abs()
+    } else {
+        -((dividend.abs() + half) / divisor.abs())
+    }
+}
+
+/// A correction for each channel, applied to every frame as a `FrameProcessor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorrectionTable {
+    channels: [Correction; 8],
+}
+
+impl CorrectionTable {
+    /// Size of the `to_bytes()` form.
+    pub const BYTES: usize = Correction::BYTES * 8;
+
+    /// Creates a table with every channel uncorrected.
+    #[must_use]
+    pub fn new() -> Self {
+        CorrectionTable::default()
+    }
+
+    pub fn set(&mut self, channel: Channel, correction: Correction) {
+        if let Some(slot) = self.channels.get_mut(channel.index()) {
+            *slot = correction;
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, channel: Channel) -> Correction {
+        self.channels
+            .get(channel.index())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Computes and stores the correction of one channel from two reference points.
+    ///
+    /// Returns `false`, leaving the table unchanged, if no correction fits the points.
+    pub fn calibrate_channel(
+        &mut self,
+        channel: Channel,
+        first: ReferencePoint,
+        second: ReferencePoint,
+    ) -> bool {
+        match Correction::from_two_points(first, second) {
+            Some(correction) => {
+                self.set(channel, correction);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the correction of one channel to a result.
+    #[must_use]
+    pub fn apply(&self, channel: Channel, raw: u16) -> u16 {
+        self.get(channel).apply(raw)
+    }
+
+    /// The corrections of `Channel0` to `Channel7` in their `Correction::to_bytes()` form.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; CorrectionTable::BYTES] {
+        let mut bytes = [0_u8; CorrectionTable::BYTES];
+        for (chunk, correction) in bytes
+            .chunks_exact_mut(Correction::BYTES)
+            .zip(&self.channels)
+        {
+            chunk.copy_from_slice(&correction.to_bytes());
+        }
+        bytes
+    }
+
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8; CorrectionTable::BYTES]) -> Self {
+        let mut table = CorrectionTable::default();
+        for (correction, chunk) in table
+            .channels
+            .iter_mut()
+            .zip(bytes.chunks_exact(Correction::BYTES))
+        {
+            if let Ok(entry) = <&[u8; Correction::BYTES]>::try_from(chunk) {
+                *correction = Correction::from_bytes(entry);
+            }
+        }
+        table
+    }
+}
+
+impl FrameProcessor for CorrectionTable {
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        for (correction, value) in self.channels.iter().zip(frame.iter_mut()) {
+            *value = correction.apply(*value);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{Correction, CorrectionTable, ReferencePoint, UNITY_GAIN};
+    use crate::{channel::Channel, filter::FrameProcessor};
+
+    const LOW: ReferencePoint = ReferencePoint {
+        measured: 210,
+        expected: 200,
+    };
+    const HIGH: ReferencePoint = ReferencePoint {
+        measured: 3890,
+        expected: 4000,
+    };
+
+    #[test]
+    fn two_point_fit_maps_the_references_onto_their_expected_codes() {
+        let correction = Correction::from_two_points(LOW, HIGH).unwrap();
+        // 3800 / 3680 in Q16.16, and 200 - 210 * gain in 1/256ths of a code
+        assert_eq!(
+            correction,
+            Correction {
+                gain: 67_673,
+                offset: -4313
+            }
+        );
+        assert_eq!(correction.apply(210), 200);
+        assert_eq!(correction.apply(3890), 4000);
+        assert_eq!(correction.apply(2050), 2100);
+        // Swapping the points only moves the offset by its rounding
+        let swapped = Correction::from_two_points(HIGH, LOW).unwrap();
+        assert_eq!(swapped.gain, correction.gain);
+        assert_eq!([swapped.apply(210), swapped.apply(3890)], [200, 4000]);
+    }
+
+    #[test]
+    fn unfittable_points_are_rejected() {
+        assert_eq!(Correction::from_two_points(LOW, LOW), None);
+        let steep = ReferencePoint {
+            measured: 211,
+            expected: u16::MAX,
+        };
+        assert_eq!(Correction::from_two_points(LOW, steep), None);
+        let mut table = CorrectionTable::new();
+        assert!(!table.calibrate_channel(Channel::Channel3, LOW, LOW));
+        assert_eq!(table.get(Channel::Channel3), Correction::IDENTITY);
+    }
+
+    #[test]
+    fn corrected_results_saturate_at_zero_and_full_scale() {
+        let correction = Correction::from_two_points(LOW, HIGH).unwrap();
+        assert_eq!(correction.apply(0), 0);
+        assert_eq!(correction.apply(4), 0);
+        assert_eq!(correction.apply(u16::MAX), u16::MAX);
+        let offset = Correction {
+            gain: UNITY_GAIN,
+            offset: 100 << 8,
+        };
+        assert_eq!(offset.apply(u16::MAX - 50), u16::MAX);
+        assert_eq!(Correction::IDENTITY.apply(u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        assert_eq!(
+            Correction::IDENTITY.to_bytes(),
+            [0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        let mut table = CorrectionTable::new();
+        assert!(table.calibrate_channel(Channel::Channel1, LOW, HIGH));
+        table.set(
+            Channel::Channel6,
+            Correction {
+                gain: -UNITY_GAIN,
+                offset: i32::MIN,
+            },
+        );
+        let bytes = table.to_bytes();
+        assert_eq!(
+            bytes.get(8..16).unwrap(),
+            table.get(Channel::Channel1).to_bytes()
+        );
+        assert_eq!(CorrectionTable::from_bytes(&bytes), table);
+    }
+
+    #[test]
+    fn table_corrects_each_channel_of_a_frame() {
+        let mut table = CorrectionTable::new();
+        assert!(table.calibrate_channel(Channel::Channel0, LOW, HIGH));
+        let mut frame = [210, 210, 3890, 0, 0, 0, 0, 3890];
+        table.process_frame(&mut frame);
+        assert_eq!(frame, [200, 210, 3890, 0, 0, 0, 0, 3890]);
+        assert_eq!(table.apply(Channel::Channel0, 3890), 4000);
+    }
+}
@@ -0,0 +1,500 @@
+//! Decoding of raw TLA2528 bus traffic into readable operations.
+//!
+//! `Decoder` turns the bytes of I2C writes and reads, for example from a logic analyzer export
+//! or a `recorder` log, into `Decoded` values whose `Display` output names the opcode, the
+//! register and the meaning of the value, such as
+//! `SingleRegisterWrite OPMODE_CFG = HighSpeedOscillator_250_xxx_Sps` or
+//! `data frame ch3 = 0x7A0`.
+//!
+//! The decoder follows writes to `DATA_CFG` and `OSR_CFG` so conversion data is split into
+//! frames of the right width, and remembers the register addressed by a read command so the
+//! following read is shown as register data.
+
+use core::fmt;
+
+use bitflags::parser::to_writer;
+use embedded_hal::i2c::ErrorKind;
+
+use crate::{
+    chip_definitions::{
+        DataConfig, GeneralConfigFlags, OpCode, Oversampling, RegisterAddress, SamplingRate,
+        SequenceConfig, SystemStatusFlags, I2C_HIGH_SPEED_MASTER_CODE,
+    },
+    recorder::Record,
+};
+
+const DATA_CONFIG_APPEND_MASK: u8 = 0b_0011_0000;
+const DATA_CONFIG_APPEND_CHANNEL_ID: u8 = 0b_0001_0000;
+const OSR_CONFIG_MASK: u8 = 0b_0000_0111;
+
+/// A register and a value written to or read from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterValue {
+    pub register: u8,
+    pub value: u8,
+}
+
+impl fmt::Display for RegisterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_register_name(f, self.register)?;
+        f.write_str(" = ")?;
+        write_register_value(f, self.register, self.value)
+    }
+}
+
+fn write_register_name(f: &mut fmt::Formatter<'_>, address: u8) -> fmt::Result {
+    match RegisterAddress::try_from(address) {
+        Ok(register) => write!(f, "{register}"),
+        Err(()) => write!(f, "register {address:#04x}"),
+    }
+}
+
+fn write_register_value(f: &mut fmt::Formatter<'_>, address: u8, value: u8) -> fmt::Result {
+    let Ok(register) = RegisterAddress::try_from(address) else {
+        return write!(f, "{value:#04x}");
+    };
+    let named = match register {
+        RegisterAddress::SystemStatus if value != 0 => {
+            return to_writer(&SystemStatusFlags::from_bits_retain(value), f);
+        }
+        RegisterAddress::GeneralConfig if value != 0 => {
+            return to_writer(&GeneralConfigFlags::from_bits_retain(value), f);
+        }
+        RegisterAddress::DataConfig => DataConfig::try_from(value).map(|v| write!(f, "{v}")),
+        RegisterAddress::OsrConfig => Oversampling::try_from(value).map(|v| write!(f, "{v}")),
+        RegisterAddress::OpModeConfig => SamplingRate::try_from(value).map(|v| write!(f, "{v}")),
+        RegisterAddress::SequenceConfig => {
+            SequenceConfig::try_from(value).map(|v| write!(f, "{v}"))
+        }
+        RegisterAddress::ChannelSelect if value < 8 => return write!(f, "ch{value}"),
+        RegisterAddress::PinConfig
+        | RegisterAddress::GpioConfig
+        | RegisterAddress::GpioDriveConfig
+        | RegisterAddress::GpOutValue
+        | RegisterAddress::GpInValue
+        | RegisterAddress::AutoSequenceChannelSelect => return write!(f, "{value:#010b}"),
+        RegisterAddress::SystemStatus
+        | RegisterAddress::GeneralConfig
+        | RegisterAddress::ChannelSelect => Err(()),
+    };
+    named.unwrap_or_else(|()| write!(f, "{value:#04x}"))
+}
+
+/// One conversion result from a data read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame {
+    /// The appended channel ID, if channel IDs are enabled.
+    pub channel_id: Option<u8>,
+    /// 12-bit result, or 16-bit when oversampling is enabled.
+    pub value: u16,
+    pub averaged: bool,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("data frame ")?;
+        if let Some(id) = self.channel_id {
+            write!(f, "ch{id} ")?;
+        }
+        if self.averaged {
+            write!(f, "= {:#06X}", self.value)
+        } else {
+            write!(f, "= {:#05X}", self.value)
+        }
+    }
+}
+
+/// Splits conversion data into frames. A trailing partial frame is not returned.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frames<'a> {
+    data: &'a [u8],
+    channel_ids: bool,
+    averaged: bool,
+}
+
+impl Frames<'_> {
+    fn frame_len(&self) -> usize {
+        if self.averaged && self.channel_ids {
+            3
+        } else {
+            2
+        }
+    }
+}
+
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided Iterator adapters need no specialisation for frames."
+)]
+impl Iterator for Frames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame_len = self.frame_len();
+        let bytes = self.data.get(..frame_len)?;
+        self.data = self.data.get(frame_len..).unwrap_or(&[]);
+        let (&high, &low, id_byte) = match *bytes {
+            [ref high, ref low] => (high, low, None),
+            [ref high, ref low, ref id] => (high, low, Some(*id)),
+            _ => return None,
+        };
+        let word = u16::from_be_bytes([high, low]);
+        let frame = if self.averaged {
+            Frame {
+                channel_id: self.channel_ids.then(|| id_byte.unwrap_or(0) >> 4_u32),
+                value: word,
+                averaged: true,
+            }
+        } else {
+            Frame {
+                channel_id: self.channel_ids.then_some(low & 0x0F),
+                value: word >> 4,
+                averaged: false,
+            }
+        };
+        Some(frame)
+    }
+}
+
+/// A decoded bus operation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Decoded<'a> {
+    /// A read command, addressing `register` for the following read.
+    RegisterRead { opcode: OpCode, register: u8 },
+    /// A command that writes, sets or clears bits of one or more consecutive registers.
+    RegisterWrite {
+        opcode: OpCode,
+        register: u8,
+        values: &'a [u8],
+    },
+    /// Register contents returned after a read command, starting at `register`.
+    RegisterData { register: u8, values: &'a [u8] },
+    /// Conversion results.
+    Data(Frames<'a>),
+    /// The I2C high-speed mode master code.
+    HighSpeedMasterCode,
+    /// A write that is not a valid command.
+    Invalid(&'a [u8]),
+    /// A transaction that failed on the bus.
+    BusError(ErrorKind),
+}
+
+impl fmt::Display for Decoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Decoded::RegisterRead { opcode, register } => {
+                write!(f, "{opcode} ")?;
+                write_register_name(f, register)
+            }
+            Decoded::RegisterWrite {
+                opcode,
+                register: first,
+                values,
+            } => {
+                write!(f, "{opcode}")?;
+                for (register, &value) in (first..=u8::MAX).zip(values) {
+                    write!(f, " {}", RegisterValue { register, value })?;
+                }
+                Ok(())
+            }
+            Decoded::RegisterData {
+                register: first,
+                values,
+            } => {
+                for (index, (register, &value)) in (first..=u8::MAX).zip(values).enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", RegisterValue { register, value })?;
+                }
+                Ok(())
+            }
+            Decoded::Data(ref frames) => {
+                for (index, frame) in frames.clone().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{frame}")?;
+                }
+                Ok(())
+            }
+            Decoded::HighSpeedMasterCode => f.write_str("high-speed master code"),
+            Decoded::Invalid(bytes) => {
+                f.write_str("invalid command")?;
+                for byte in bytes {
+                    write!(f, " {byte:02X}")?;
+                }
+                Ok(())
+            }
+            Decoded::BusError(kind) => write!(f, "bus error: {kind}"),
+        }
+    }
+}
+
+/// Decodes a stream of bus operations addressed to one TLA2528.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Decoder {
+    data_config: u8,
+    osr_config: u8,
+    register_pointer: Option<u8>,
+}
+
+impl Decoder {
+    /// Creates a decoder assuming the device starts with its reset configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    /// Decodes the bytes of a write to `address`.
+    pub fn decode_write<'a>(&mut self, address: u8, data: &'a [u8]) -> Decoded<'a> {
+        self.register_pointer = None;
+        if address & !0b_0000_0011 == I2C_HIGH_SPEED_MASTER_CODE {
+            return Decoded::HighSpeedMasterCode;
+        }
+        let [opcode_byte, register, ref values @ ..] = *data else {
+            return Decoded::Invalid(data);
+        };
+        match OpCode::try_from(opcode_byte) {
+            Ok(opcode @ (OpCode::SingleRegisterRead | OpCode::ReadContinuousRegisters)) => {
+                self.register_pointer = Some(register);
+                Decoded::RegisterRead { opcode, register }
+            }
+            Ok(
+                opcode @ (OpCode::SingleRegisterWrite
+                | OpCode::WriteContinuousRegisters
+                | OpCode::SetBit
+                | OpCode::ClearBit),
+            ) if !values.is_empty() => {
+                self.track(opcode, register, values);
+                Decoded::RegisterWrite {
+                    opcode,
+                    register,
+                    values,
+                }
+            }
+            Ok(_) | Err(()) => Decoded::Invalid(data),
+        }
+    }
+
+    /// Decodes the bytes returned by a read, as register data if it follows a read command or
+    /// as conversion data otherwise.
+    pub fn decode_read<'a>(&mut self, data: &'a [u8]) -> Decoded<'a> {
+        if let Some(register) = self.register_pointer.take() {
+            return Decoded::RegisterData {
+                register,
+                values: data,
+            };
+        }
+        Decoded::Data(Frames {
+            data,
+            channel_ids: self.data_config & DATA_CONFIG_APPEND_MASK
+                == DATA_CONFIG_APPEND_CHANNEL_ID,
+            averaged: self.osr_config & OSR_CONFIG_MASK != 0,
+        })
+    }
+
+    /// Decodes an entry of a `recorder` log.
+    pub fn decode_record<'a>(&mut self, record: Record<'a>) -> Decoded<'a> {
+        match record {
+            Record::Write { address, data, .. } => self.decode_write(address, data),
+            Record::Read { data, .. } => self.decode_read(data),
+            Record::Error { address, kind } => {
+                if address & !0b_0000_0011 == I2C_HIGH_SPEED_MASTER_CODE {
+                    Decoded::HighSpeedMasterCode
+                } else {
+                    self.register_pointer = None;
+                    Decoded::BusError(kind)
+                }
+            }
+        }
+    }
+
+    fn track(&mut self, opcode: OpCode, first: u8, values: &[u8]) {
+        for (register, &value) in (first..=u8::MAX).zip(values) {
+            let shadow = match register {
+                x if x == RegisterAddress::DataConfig.value() => &mut self.data_config,
+                x if x == RegisterAddress::OsrConfig.value() => &mut self.osr_config,
+                _ => continue,
+            };
+            *shadow = match opcode {
+                OpCode::SetBit => *shadow | value,
+                OpCode::ClearBit => *shadow & !value,
+                OpCode::SingleRegisterWrite
+                | OpCode::WriteContinuousRegisters
+                | OpCode::SingleRegisterRead
+                | OpCode::ReadContinuousRegisters => value,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+
+    use super::Decoder;
+    use crate::{
+        channel::Channel,
+        chip_definitions::{Oversampling, RegisterAddress},
+        recorder::{Record, Recorder, Records},
+        sim::SimulatedTla2528,
+        Tla2528,
+    };
+
+    const ADDRESS: u8 = 0x10;
+
+    fn write(decoder: &mut Decoder, data: &[u8]) -> String {
+        decoder.decode_write(ADDRESS, data).to_string()
+    }
+
+    fn read(decoder: &mut Decoder, data: &[u8]) -> String {
+        decoder.decode_read(data).to_string()
+    }
+
+    #[test]
+    fn writes_name_the_opcode_register_and_value() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            write(&mut decoder, &[0x08, 0x04, 0x04]),
+            "SingleRegisterWrite OPMODE_CFG = HighSpeedOscillator_250_xxx_Sps"
+        );
+        assert_eq!(
+            write(&mut decoder, &[0x18, 0x0B, 0x04]),
+            "SetBit GPO_VALUE = 0b00000100"
+        );
+        assert_eq!(
+            write(&mut decoder, &[0x20, 0x01, 0x02]),
+            "ClearBit GENERAL_CFG = CALIBRATE_ADC_OFFSET"
+        );
+        assert_eq!(
+            write(&mut decoder, &[0x28, 0x02, 0x10, 0x04]),
+            "WriteContinuousRegisters DATA_CFG = NormalDataAddChannelID OSR_CFG = Ratio16"
+        );
+    }
+
+    #[test]
+    fn reads_are_followed_by_register_data() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            write(&mut decoder, &[0x10, 0x03]),
+            "SingleRegisterRead OSR_CFG"
+        );
+        assert_eq!(read(&mut decoder, &[0x04]), "OSR_CFG = Ratio16");
+        assert_eq!(
+            write(&mut decoder, &[0x30, 0x00]),
+            "ReadContinuousRegisters SYSTEM_STATUS"
+        );
+        assert_eq!(
+            read(&mut decoder, &[0x81, 0x02, 0x10]),
+            "SYSTEM_STATUS = _RESERVED_07 | BROWNOUT_RESET, GENERAL_CFG = CALIBRATE_ADC_OFFSET, \
+             DATA_CFG = NormalDataAddChannelID"
+        );
+    }
+
+    #[test]
+    fn data_frames_follow_the_configured_width() {
+        let mut decoder = Decoder::new();
+        write(&mut decoder, &[0x08, 0x02, 0x10]);
+        assert_eq!(
+            read(&mut decoder, &[0x7A, 0x03, 0x12, 0x35]),
+            "data frame ch3 = 0x7A0, data frame ch5 = 0x123"
+        );
+        write(&mut decoder, &[0x18, 0x03, 0x04]);
+        assert_eq!(
+            read(&mut decoder, &[0x12, 0x34, 0x30, 0xAB, 0xCD, 0x70]),
+            "data frame ch3 = 0x1234, data frame ch7 = 0xABCD"
+        );
+        // Without channel IDs, oversampled results take two bytes
+        write(&mut decoder, &[0x08, 0x02, 0x00]);
+        assert_eq!(read(&mut decoder, &[0x12, 0x34]), "data frame = 0x1234");
+    }
+
+    #[test]
+    fn unknown_registers_opcodes_and_the_master_code() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            write(&mut decoder, &[0x08, 0x7F, 0x01]),
+            "SingleRegisterWrite register 0x7f = 0x01"
+        );
+        assert_eq!(
+            write(&mut decoder, &[0x55, 0x01, 0x02]),
+            "invalid command 55 01 02"
+        );
+        assert_eq!(write(&mut decoder, &[0x08]), "invalid command 08");
+        assert_eq!(
+            decoder.decode_write(0x05, &[]).to_string(),
+            "high-speed master code"
+        );
+        // Devices do not acknowledge the master code, so recorders log it as an error
+        let nack = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+        let master_code = Record::Error {
+            address: 0x04,
+            kind: nack,
+        };
+        assert_eq!(
+            decoder.decode_record(master_code).to_string(),
+            "high-speed master code"
+        );
+        let failed = Record::Error {
+            address: ADDRESS,
+            kind: nack,
+        };
+        assert!(decoder
+            .decode_record(failed)
+            .to_string()
+            .starts_with("bus error: "));
+    }
+
+    #[test]
+    fn decodes_a_recorded_session() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_input(Channel::Channel3, 0x7A0);
+        let mut buffer = [0_u8; 256];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        let mut adc = Tla2528::new(&mut recorder, ADDRESS);
+        adc.read_register(RegisterAddress::OsrConfig).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        adc.acquire_channel_data(Channel::Channel3).unwrap();
+        adc.set_oversampling_ratio(Oversampling::Ratio16).unwrap();
+        adc.acquire_channel_data(Channel::Channel3).unwrap();
+
+        let mut decoder = Decoder::new();
+        let lines: Vec<String> = Records::new(recorder.log())
+            .map(|record| decoder.decode_record(record).to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "SingleRegisterRead OSR_CFG",
+                "OSR_CFG = Ratio0",
+                "SingleRegisterWrite SEQUENCE_CFG = Manual",
+                "SingleRegisterWrite GPIO_CFG = 0b00000000",
+                "SingleRegisterWrite GPO_DRIVE_CFG = 0b00000000",
+                "SingleRegisterWrite PIN_CFG = 0b00000000",
+                "SingleRegisterWrite DATA_CFG = NormalDataAddChannelID",
+                "SingleRegisterWrite SEQUENCE_CFG = Manual",
+                "SingleRegisterWrite CHANNEL_SEL = ch3",
+                "data frame ch3 = 0x7A0",
+                "SingleRegisterWrite OSR_CFG = Ratio16",
+                "SingleRegisterWrite CHANNEL_SEL = ch3",
+                "data frame ch3 = 0x7A00",
+            ]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Detection of open and shorted inputs.
+//!
+//! A floating input holds whatever charge it last had and reads as a plausible value.
+//! `Tla2528::check_input()` drives the pin high as an output, switches it back to an analog
+//! input and samples, then does the same driving low. An open input keeps the charge and
+//! follows the drive; a connected source pulls it back to the same level both times.
+
+use crate::chip_definitions::Resolution;
+
+/// Classification of an input from the results after driving it high and low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputState {
+    /// A source holds the input away from both rails.
+    Connected,
+    /// Nothing holds the input; it follows the drive.
+    Open,
+    ShortedToGround,
+    ShortedToSupply,
+}
+
+/// The results of an input check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputCheck {
+    pub state: InputState,
+    /// Result after the input was driven high.
+    pub after_high: u16,
+    /// Result after the input was driven low.
+    pub after_low: u16,
+}
+
+impl InputCheck {
+    /// Classifies an input. It is open if the results differ by more than half of full scale,
+    /// and shorted if both are within 1/16 of full scale of the same rail.
+    #[must_use]
+    pub fn classify(after_high: u16, after_low: u16, resolution: Resolution) -> Self {
+        let full_scale = resolution.full_scale();
+        let margin = full_scale / 16;
+        let high = u32::from(after_high);
+        let low = u32::from(after_low);
+        let state = if high > low + full_scale / 2 {
+            InputState::Open
+        } else if high.max(low) < margin {
+            InputState::ShortedToGround
+        } else if high.min(low) + margin >= full_scale {
+            InputState::ShortedToSupply
+        } else {
+            InputState::Connected
+        };
+        InputCheck {
+            state,
+            after_high,
+            after_low,
+        }
+    }
+}
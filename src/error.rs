@@ -1,14 +1,126 @@
+use core::fmt;
+
+use embedded_hal::{i2c, spi};
+
+use crate::chip_definitions::RegisterAddress;
+
+/// The bus operation that was in progress when a bus error occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation {
+    RegisterRead(RegisterAddress),
+    RegisterWrite(RegisterAddress),
+    DataRead,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operation::RegisterRead(register) => write!(f, "read of register {register}"),
+            Operation::RegisterWrite(register) => write!(f, "write of register {register}"),
+            Operation::DataRead => f.write_str("conversion data read"),
+        }
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Error<E> {
-    I2cError(E),
-    _DataItemsMisOrdered,
+    /// An error from the I2C or SPI bus.
+    Bus {
+        operation: Operation,
+        source: E,
+    },
+    DataItemsMisOrdered,
     IncorrectChannelAddress,
     InvalidChannelAddress,
+    CalibrationTimeout,
+}
+
+impl<E> Error<E> {
+    pub(crate) fn bus(operation: Operation, source: E) -> Self {
+        Error::Bus { operation, source }
+    }
+
+    /// The bus operation that failed, if this is a bus error.
+    pub fn operation(&self) -> Option<Operation> {
+        match *self {
+            Error::Bus { operation, .. } => Some(operation),
+            Error::DataItemsMisOrdered
+            | Error::IncorrectChannelAddress
+            | Error::InvalidChannelAddress
+            | Error::CalibrationTimeout => None,
+        }
+    }
+}
+
+impl<E> i2c::Error for Error<E>
+where
+    E: i2c::Error,
+{
+    /// Maps I2C errors to the underlying bus error kind. Errors in the data returned by the
+    /// device are reported as `ErrorKind::Other`.
+    fn kind(&self) -> i2c::ErrorKind {
+        match *self {
+            Error::Bus { ref source, .. } => source.kind(),
+            Error::DataItemsMisOrdered
+            | Error::IncorrectChannelAddress
+            | Error::InvalidChannelAddress
+            | Error::CalibrationTimeout => i2c::ErrorKind::Other,
+        }
+    }
 }
 
-impl<E> From<E> for Error<E> {
-    fn from(other: E) -> Self {
-        Error::I2cError(other)
+impl<E> spi::Error for Error<E>
+where
+    E: spi::Error,
+{
+    /// Maps SPI errors to the underlying bus error kind. Errors in the data returned by the
+    /// device are reported as `ErrorKind::Other`.
+    fn kind(&self) -> spi::ErrorKind {
+        match *self {
+            Error::Bus { ref source, .. } => source.kind(),
+            Error::DataItemsMisOrdered
+            | Error::IncorrectChannelAddress
+            | Error::InvalidChannelAddress
+            | Error::CalibrationTimeout => spi::ErrorKind::Other,
+        }
     }
 }
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            #[allow(
+                clippy::use_debug,
+                reason = "I2C and SPI errors only share Debug, which HALs use to describe them."
+            )]
+            Error::Bus {
+                operation,
+                ref source,
+            } => write!(f, "bus error during {operation}: {source:?}"),
+            Error::DataItemsMisOrdered => f.write_str("conversion data items out of order"),
+            Error::IncorrectChannelAddress => {
+                f.write_str("conversion data did not contain the requested channel")
+            }
+            Error::InvalidChannelAddress => {
+                f.write_str("conversion data has an invalid channel ID")
+            }
+            Error::CalibrationTimeout => f.write_str("offset calibration did not complete"),
+        }
+    }
+}
+
+/// `source()` is not implemented: it would require `E: core::error::Error + 'static`, which most
+/// embedded HAL bus errors do not implement, and without specialization that bound would apply
+/// to every `Error<E>`. The bus error is described by `Display` instead, and can be matched out of
+/// `Error::Bus`.
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "source() is deliberately omitted, the other provided methods are deprecated or unstable."
+)]
+impl<E> core::error::Error for Error<E> where E: fmt::Debug {}
@@ -0,0 +1,75 @@
+//! Switched sensor excitation from the TLA2528's own pins.
+//!
+//! Sensors such as thermistor dividers can be powered from a pin configured as a push-pull
+//! output, so they draw current only while being measured. `Tla2528::acquire_excited()` drives
+//! the pin high, waits for the divider to settle, samples the analog channels in manual mode
+//! and restores the pin's previous configuration, de-energising the sensor.
+//!
+//! `Tla2528::measure_bridge()` excites a `Bridge` from two pins, reversing the polarity between
+//! two measurements to cancel offsets.
+
+use crate::{channel::Channel, chip_definitions::Resolution};
+
+/// A pin powering a sensor and the time the sensor needs to settle once powered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Excitation {
+    pub pin: Channel,
+    pub settle_ns: u32,
+}
+
+/// A resistive bridge, such as a load cell, excited from two pins so the excitation can be
+/// reversed.
+///
+/// Measuring with both polarities and taking half the difference cancels offsets that do not
+/// follow the excitation, such as thermocouple voltages at the bridge connections. Because the
+/// pins switch between ground and AVDD, which is also the ADC reference, results are
+/// ratiometric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bridge {
+    /// Pin driven high in the forward phase.
+    pub excitation_positive: Channel,
+    /// Pin driven high in the reverse phase.
+    pub excitation_negative: Channel,
+    /// Output that rises with the measured quantity in the forward phase.
+    pub sense_positive: Channel,
+    pub sense_negative: Channel,
+    /// Time to wait after each polarity change.
+    pub settle_ns: u32,
+    pub resolution: Resolution,
+}
+
+/// An offset-cancelled bridge measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BridgeReading {
+    /// Bridge output in codes with offsets removed.
+    pub differential: i32,
+    /// Offset common to both phases in codes, from connections or the sense amplifier.
+    pub offset: i32,
+    /// Bridge output relative to the excitation, in microvolts per volt.
+    pub microvolts_per_volt: i32,
+}
+
+impl Bridge {
+    /// Combines the differential results (`sense_positive - sense_negative`) of the forward
+    /// and reverse phases.
+    #[must_use]
+    pub fn combine(&self, forward: i32, reverse: i32) -> BridgeReading {
+        let saturate = |value: i64| {
+            i32::try_from(value).unwrap_or(if value < 0 { i32::MIN } else { i32::MAX })
+        };
+        let differential = (i64::from(forward) - i64::from(reverse)) / 2;
+        BridgeReading {
+            differential: saturate(differential),
+            offset: saturate(i64::from(reverse) + differential),
+            microvolts_per_volt: saturate(
+                differential * 1_000_000 / i64::from(self.resolution.full_scale()),
+            ),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Per-channel software filtering of acquired frames.
+//!
+//! `FilterBank` holds an independent filter for each `Channel`, selected with `FilterKind`, and
+//! is applied to each frame returned by `Tla2528::acquire_processed_data()`. All filters use
+//! integer arithmetic and fixed-size state, so the bank needs no allocator.
+
+use crate::channel::Channel;
+
+/// Longest window supported by the moving average and median filters.
+pub const MAX_WINDOW: usize = 16;
+
+/// Fractional bits kept in the IIR filter state.
+const IIR_FRACTION_BITS: u32 = 8;
+
+/// Something that transforms or observes every frame returned by auto-sequence acquisition.
+pub trait FrameProcessor {
+    /// Processes a frame of results indexed by channel, possibly rewriting it in place.
+    fn process_frame(&mut self, frame: &mut [u16; 8]);
+}
+
+impl<P> FrameProcessor for &mut P
+where
+    P: FrameProcessor + ?Sized,
+{
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        (**self).process_frame(frame);
+    }
+}
+
+/// Runs the first processor and then the second, such as a filter bank followed by a monitor.
+impl<A, B> FrameProcessor for (A, B)
+where
+    A: FrameProcessor,
+    B: FrameProcessor,
+{
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        self.0.process_frame(frame);
+        self.1.process_frame(frame);
+    }
+}
+
+/// The filter applied to one channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilterKind {
+    /// Samples pass through unchanged.
+    #[default]
+    Bypass,
+    /// Mean of the last `length` samples, limited to `MAX_WINDOW`.
+    MovingAverage { length: u8 },
+    /// Median of the last `length` samples, limited to `MAX_WINDOW`. Odd lengths avoid
+    /// averaging the two middle samples.
+    Median { length: u8 },
+    /// Single-pole low-pass filter, `y += (x - y) / 2^shift`, with `shift` limited to 15.
+    Iir { shift: u8 },
+}
+
+/// Filter state for a single channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelFilter {
+    kind: FilterKind,
+    window: [u16; MAX_WINDOW],
+    filled: usize,
+    next: usize,
+    sum: u32,
+    iir_state: i32,
+}
+
+impl ChannelFilter {
+    #[must_use]
+    pub fn new(kind: FilterKind) -> Self {
+        ChannelFilter {
+            kind,
+            ..ChannelFilter::default()
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// Discards the filter history. The next sample is passed through unchanged.
+    pub fn reset(&mut self) {
+        *self = ChannelFilter::new(self.kind);
+    }
+
+    /// Adds a sample and returns the filtered value.
+    pub fn process(&mut self, sample: u16) -> u16 {
+        match self.kind {
+            FilterKind::Bypass => sample,
+            FilterKind::MovingAverage { length } => {
+                let evicted = self.push(sample, length);
+                self.sum = self.sum + u32::from(sample) - u32::from(evicted);
+                let filled = u32::try_from(self.filled).unwrap_or(1);
+                u16::try_from((self.sum + filled / 2) / filled).unwrap_or(u16::MAX)
+            }
+            FilterKind::Median { length } => {
+                self.push(sample, length);
+                let mut sorted = [0_u16; MAX_WINDOW];
+                let history = sorted.get_mut(..self.filled).unwrap_or_default();
+                history.copy_from_slice(self.window.get(..self.filled).unwrap_or_default());
+                history.sort_unstable();
+                let middle = history.len() / 2;
+                match (history.get(middle), history.get(middle.wrapping_sub(1))) {
+                    (Some(&upper), Some(&lower)) if history.len() % 2 == 0 => {
+                        let sum = u32::from(upper) + u32::from(lower);
+                        u16::try_from(sum.div_ceil(2)).unwrap_or(u16::MAX)
+                    }
+                    (Some(&value), _) => value,
+                    (None, _) => sample,
+                }
+            }
+            FilterKind::Iir { shift } => {
+                let input = i32::from(sample) << IIR_FRACTION_BITS;
+                if self.filled == 0 {
+                    self.filled = 1;
+                    self.iir_state = input;
+                } else {
+                    self.iir_state += (input - self.iir_state) >> u32::from(shift.min(15));
+                }
+                let rounded =
+                    (self.iir_state + (1 << (IIR_FRACTION_BITS - 1))) >> IIR_FRACTION_BITS;
+                u16::try_from(rounded).unwrap_or(u16::MAX)
+            }
+        }
+    }
+
+    /// Stores a sample in the window and returns the sample it replaced, or 0 while the window
+    /// is filling.
+    fn push(&mut self, sample: u16, length: u8) -> u16 {
+        let capacity = usize::from(length).clamp(1, MAX_WINDOW);
+        let evicted = if self.filled < capacity {
+            self.filled += 1;
+            0
+        } else {
+            self.window.get(self.next).copied().unwrap_or(0)
+        };
+        if let Some(slot) = self.window.get_mut(self.next) {
+            *slot = sample;
+        }
+        self.next = (self.next + 1) % capacity;
+        evicted
+    }
+}
+
+/// An independent filter for each channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterBank {
+    filters: [ChannelFilter; 8],
+}
+
+impl FilterBank {
+    /// Creates a bank with every channel set to `FilterKind::Bypass`.
+    #[must_use]
+    pub fn new() -> Self {
+        FilterBank::default()
+    }
+
+    /// Creates a bank applying the same filter to every channel.
+    #[must_use]
+    pub fn uniform(kind: FilterKind) -> Self {
+        FilterBank {
+            filters: [ChannelFilter::new(kind); 8],
+        }
+    }
+
+    /// Changes the filter of one channel, discarding its history.
+    pub fn set_filter(&mut self, channel: Channel, kind: FilterKind) {
+        if let Some(filter) = self.filters.get_mut(channel.index()) {
+            *filter = ChannelFilter::new(kind);
+        }
+    }
+
+    #[must_use]
+    pub fn filter(&self, channel: Channel) -> FilterKind {
+        self.filters
+            .get(channel.index())
+            .map_or(FilterKind::Bypass, ChannelFilter::kind)
+    }
+
+    /// Discards the history of every channel.
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(ChannelFilter::reset);
+    }
+
+    /// Discards the history of one channel.
+    pub fn reset_channel(&mut self, channel: Channel) {
+        if let Some(filter) = self.filters.get_mut(channel.index()) {
+            filter.reset();
+        }
+    }
+
+    /// Adds a sample for one channel and returns the filtered value.
+    pub fn process(&mut self, channel: Channel, sample: u16) -> u16 {
+        self.filters
+            .get_mut(channel.index())
+            .map_or(sample, |filter| filter.process(sample))
+    }
+}
+
+impl FrameProcessor for FilterBank {
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        for (filter, value) in self.filters.iter_mut().zip(frame.iter_mut()) {
+            *value = filter.process(*value);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{ChannelFilter, FilterBank, FilterKind, FrameProcessor};
+    use crate::{channel::Channel, statistics::Statistics};
+
+    fn outputs<const N: usize>(filter: &mut ChannelFilter, samples: [u16; N]) -> [u16; N] {
+        samples.map(|sample| filter.process(sample))
+    }
+
+    #[test]
+    fn moving_average_fills_its_window_then_follows_a_step() {
+        let mut filter = ChannelFilter::new(FilterKind::MovingAverage { length: 4 });
+        // While filling, the mean covers only the samples seen so far
+        assert_eq!(outputs(&mut filter, [100, 200, 300]), [100, 150, 200]);
+        filter.reset();
+        assert_eq!(
+            outputs(&mut filter, [0, 0, 0, 0, 1000, 1000, 1000, 1000, 1000]),
+            [0, 0, 0, 0, 250, 500, 750, 1000, 1000]
+        );
+    }
+
+    #[test]
+    fn median_fills_its_window_and_rejects_spikes() {
+        let mut filter = ChannelFilter::new(FilterKind::Median { length: 3 });
+        // Two samples have no middle one, so the two are averaged
+        assert_eq!(outputs(&mut filter, [10, 21]), [10, 16]);
+        filter.reset();
+        assert_eq!(
+            outputs(&mut filter, [10, 10, 10, 4000, 10, 10]),
+            [10, 10, 10, 10, 10, 10]
+        );
+        assert_eq!(outputs(&mut filter, [100, 100, 100]), [10, 100, 100]);
+    }
+
+    #[test]
+    fn iir_starts_at_the_first_sample_and_settles_on_a_step() {
+        let mut filter = ChannelFilter::new(FilterKind::Iir { shift: 2 });
+        assert_eq!(
+            outputs(&mut filter, [0, 1000, 1000, 1000]),
+            [0, 250, 438, 578]
+        );
+        let settled = (0..40_u8).fold(0, |_, _| filter.process(1000));
+        assert_eq!(settled, 1000);
+        filter.reset();
+        assert_eq!(filter.process(500), 500);
+    }
+
+    #[test]
+    fn bank_filters_channels_independently() {
+        let mut bank = FilterBank::new();
+        bank.set_filter(Channel::Channel1, FilterKind::MovingAverage { length: 2 });
+        assert_eq!(bank.filter(Channel::Channel0), FilterKind::Bypass);
+        let mut frame = [100_u16; 8];
+        bank.process_frame(&mut frame);
+        frame = [300_u16; 8];
+        bank.process_frame(&mut frame);
+        assert_eq!(frame, [300, 200, 300, 300, 300, 300, 300, 300]);
+    }
+
+    #[test]
+    fn chained_processors_see_the_filtered_frame() {
+        let mut bank = FilterBank::uniform(FilterKind::MovingAverage { length: 2 });
+        let mut statistics = Statistics::new();
+        let mut chain = (&mut bank, &mut statistics);
+        for value in [100_u16, 300, 300] {
+            let mut frame = [value; 8];
+            chain.process_frame(&mut frame);
+        }
+        let channel = statistics.channel(Channel::Channel5);
+        assert_eq!(channel.min(), Some(100));
+        assert_eq!(channel.max(), Some(300));
+        assert_eq!(channel.count(), 3);
+        assert_eq!(channel.mean(), Some(200));
+    }
+}
@@ -0,0 +1,372 @@
+//! Decoding of buttons on a resistor ladder.
+//!
+//! Several buttons can share one input through a resistor ladder, each pulling the input to a
+//! different level. `Keypad` maps windows of results to key IDs and turns a stream of results
+//! from one channel, from `Tla2528::acquire_channel_data()` or auto-sequence frames, into
+//! debounced press, release and long-press events.
+//!
+//! Results that match neither a key nor the idle window, as when two keys are held at once,
+//! are rejected: no key is reported until a single key is held again. Debounce and long-press
+//! times are counted in samples, so they scale with the acquisition rate.
+
+use crate::channel::Channel;
+
+/// An inclusive range of results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeRange {
+    pub low: u16,
+    pub high: u16,
+}
+
+impl CodeRange {
+    #[must_use]
+    pub const fn new(low: u16, high: u16) -> Self {
+        CodeRange { low, high }
+    }
+
+    #[must_use]
+    pub fn contains(&self, code: u16) -> bool {
+        (self.low..=self.high).contains(&code)
+    }
+}
+
+/// A key and the results it produces when held alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+    pub id: u8,
+    pub codes: CodeRange,
+}
+
+impl Key {
+    #[must_use]
+    pub const fn new(id: u8, codes: CodeRange) -> Self {
+        Key { id, codes }
+    }
+}
+
+/// A change in the state of the keypad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyEvent {
+    Pressed(u8),
+    Released(u8),
+    /// The key has been held for the long-press time. Reported once per press.
+    LongPress(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Input {
+    Idle,
+    Key(u8),
+    /// Not a key or idle, such as several keys held at once.
+    Rejected,
+}
+
+/// Debounced decoding of one resistor-ladder channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Keypad<'a> {
+    channel: Channel,
+    keys: &'a [Key],
+    idle: CodeRange,
+    debounce_samples: u16,
+    long_press_samples: Option<u32>,
+    candidate: Input,
+    candidate_samples: u16,
+    stable: Input,
+    held_samples: u32,
+    long_press_reported: bool,
+}
+
+impl<'a> Keypad<'a> {
+    /// Creates a decoder for `channel`, which reads in the `idle` range when no key is held.
+    ///
+    /// Inputs must be stable for 3 samples to be accepted and long presses are not reported
+    /// until `with_long_press()` is used.
+    #[must_use]
+    pub const fn new(channel: Channel, keys: &'a [Key], idle: CodeRange) -> Self {
+        Keypad {
+            channel,
+            keys,
+            idle,
+            debounce_samples: 3,
+            long_press_samples: None,
+            candidate: Input::Idle,
+            candidate_samples: 0,
+            stable: Input::Idle,
+            held_samples: 0,
+            long_press_reported: false,
+        }
+    }
+
+    /// Sets how many consecutive samples an input must be seen for to be accepted.
+    #[must_use]
+    pub const fn with_debounce(self, samples: u16) -> Self {
+        Keypad {
+            debounce_samples: samples,
+            ..self
+        }
+    }
+
+    /// Sets how many samples a key must be held for to report a long press.
+    #[must_use]
+    pub const fn with_long_press(self, samples: u32) -> Self {
+        Keypad {
+            long_press_samples: Some(samples),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// The key currently held, after debouncing.
+    #[must_use]
+    pub fn pressed(&self) -> Option<u8> {
+        match self.stable {
+            Input::Key(id) => Some(id),
+            Input::Idle | Input::Rejected => None,
+        }
+    }
+
+    /// Whether the current input is rejected, as when several keys are held.
+    #[must_use]
+    pub fn is_rejected(&self) -> bool {
+        self.stable == Input::Rejected
+    }
+
+    /// Returns to the idle state without reporting a release.
+    pub fn reset(&mut self) {
+        self.candidate = Input::Idle;
+        self.candidate_samples = 0;
+        self.stable = Input::Idle;
+        self.held_samples = 0;
+        self.long_press_reported = false;
+    }
+
+    /// Processes a result from the keypad channel.
+    ///
+    /// Moving from one key directly to another reports the release on this sample and the
+    /// press on the next.
+    pub fn update(&mut self, code: u16) -> Option<KeyEvent> {
+        let input = self.classify(code);
+        if input == self.candidate {
+            self.candidate_samples = self.candidate_samples.saturating_add(1);
+        } else {
+            self.candidate = input;
+            self.candidate_samples = 1;
+        }
+        if let Input::Key(id) = self.stable {
+            self.held_samples = self.held_samples.saturating_add(1);
+            if self.candidate != self.stable && self.candidate_samples >= self.debounce_samples {
+                self.stable = match self.candidate {
+                    Input::Rejected => Input::Rejected,
+                    Input::Idle | Input::Key(_) => Input::Idle,
+                };
+                return Some(KeyEvent::Released(id));
+            }
+            let long_press = self
+                .long_press_samples
+                .is_some_and(|samples| self.held_samples >= samples);
+            if long_press && !self.long_press_reported {
+                self.long_press_reported = true;
+                return Some(KeyEvent::LongPress(id));
+            }
+            return None;
+        }
+        if self.candidate == self.stable || self.candidate_samples < self.debounce_samples {
+            return None;
+        }
+        self.stable = self.candidate;
+        match self.stable {
+            Input::Key(id) => {
+                self.held_samples = 0;
+                self.long_press_reported = false;
+                Some(KeyEvent::Pressed(id))
+            }
+            Input::Idle | Input::Rejected => None,
+        }
+    }
+
+    /// Processes the keypad channel's result from a frame of results indexed by channel.
+    pub fn update_frame(&mut self, frame: &[u16; 8]) -> Option<KeyEvent> {
+        let code = frame.get(self.channel.index()).copied()?;
+        self.update(code)
+    }
+
+    fn classify(&self, code: u16) -> Input {
+        if self.idle.contains(code) {
+            return Input::Idle;
+        }
+        self.keys
+            .iter()
+            .find(|key| key.codes.contains(code))
+            .map_or(Input::Rejected, |key| Input::Key(key.id))
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{CodeRange, Key, KeyEvent, Keypad};
+    use crate::channel::Channel;
+
+    const KEYS: [Key; 3] = [
+        Key::new(1, CodeRange::new(500, 700)),
+        Key::new(2, CodeRange::new(1500, 1700)),
+        Key::new(3, CodeRange::new(2500, 2700)),
+    ];
+    const IDLE_CODES: CodeRange = CodeRange::new(3900, 4095);
+
+    const IDLE: u16 = 4000;
+    const KEY_1: u16 = 600;
+    const KEY_2: u16 = 1600;
+    /// Keys 1 and 2 held together, which matches no key.
+    const KEYS_1_AND_2: u16 = 1100;
+
+    fn keypad() -> Keypad<'static> {
+        Keypad::new(Channel::Channel3, &KEYS, IDLE_CODES)
+    }
+
+    /// Feeds results to the keypad and returns the events with the index of their sample.
+    fn run(keypad: &mut Keypad<'_>, codes: &[u16]) -> Vec<(usize, KeyEvent)> {
+        codes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &code)| keypad.update(code).map(|event| (index, event)))
+            .collect()
+    }
+
+    #[test]
+    fn presses_and_releases_are_debounced() {
+        let mut keypad = keypad();
+        // Contact bounce restarts the count
+        let events = run(
+            &mut keypad,
+            &[
+                IDLE, KEY_1, IDLE, KEY_1, KEY_1, KEY_1, KEY_1, IDLE, IDLE, KEY_1, IDLE, IDLE, IDLE,
+            ],
+        );
+        assert_eq!(
+            events,
+            [(5, KeyEvent::Pressed(1)), (12, KeyEvent::Released(1))]
+        );
+        assert_eq!(keypad.pressed(), None);
+    }
+
+    #[test]
+    fn debounce_length_is_configurable() {
+        let mut keypad = keypad().with_debounce(1);
+        assert_eq!(
+            run(&mut keypad, &[KEY_2, IDLE]),
+            [(0, KeyEvent::Pressed(2)), (1, KeyEvent::Released(2))]
+        );
+    }
+
+    #[test]
+    fn long_press_is_reported_once_per_press() {
+        let mut keypad = keypad().with_long_press(4);
+        let mut codes = [KEY_1; 20];
+        codes[15..].fill(IDLE);
+        assert_eq!(
+            run(&mut keypad, &codes),
+            [
+                (2, KeyEvent::Pressed(1)),
+                (6, KeyEvent::LongPress(1)),
+                (17, KeyEvent::Released(1))
+            ]
+        );
+        // A short press does not reach the long-press time
+        assert_eq!(
+            run(&mut keypad, &[KEY_1, KEY_1, KEY_1, IDLE, IDLE, IDLE]),
+            [(2, KeyEvent::Pressed(1)), (5, KeyEvent::Released(1))]
+        );
+    }
+
+    #[test]
+    fn moving_between_keys_releases_then_presses() {
+        let mut keypad = keypad();
+        assert_eq!(
+            run(
+                &mut keypad,
+                &[KEY_1, KEY_1, KEY_1, KEY_2, KEY_2, KEY_2, KEY_2]
+            ),
+            [
+                (2, KeyEvent::Pressed(1)),
+                (5, KeyEvent::Released(1)),
+                (6, KeyEvent::Pressed(2))
+            ]
+        );
+        assert_eq!(keypad.pressed(), Some(2));
+    }
+
+    #[test]
+    fn ambiguous_codes_are_rejected() {
+        let mut keypad = keypad();
+        assert_eq!(run(&mut keypad, &[KEYS_1_AND_2; 3]), []);
+        assert!(keypad.is_rejected());
+        assert_eq!(keypad.pressed(), None);
+        // Releasing one of the keys reports the other
+        assert_eq!(run(&mut keypad, &[KEY_2; 3]), [(2, KeyEvent::Pressed(2))]);
+        // A second key joining a held one releases it, and nothing is pressed until one key
+        // is held alone again
+        assert_eq!(
+            run(&mut keypad, &[KEYS_1_AND_2; 4]),
+            [(2, KeyEvent::Released(2))]
+        );
+        assert!(keypad.is_rejected());
+        assert_eq!(
+            run(&mut keypad, &[IDLE, IDLE, IDLE, KEY_1, KEY_1, KEY_1]),
+            [(5, KeyEvent::Pressed(1))]
+        );
+        assert!(!keypad.is_rejected());
+    }
+
+    #[test]
+    fn short_glitches_do_not_release_a_held_key() {
+        let mut keypad = keypad();
+        let events = run(
+            &mut keypad,
+            &[
+                KEY_1,
+                KEY_1,
+                KEY_1,
+                KEYS_1_AND_2,
+                KEYS_1_AND_2,
+                KEY_1,
+                IDLE,
+                IDLE,
+                KEY_1,
+            ],
+        );
+        assert_eq!(events, [(2, KeyEvent::Pressed(1))]);
+        assert_eq!(keypad.pressed(), Some(1));
+    }
+
+    #[test]
+    fn frames_are_read_from_the_keypad_channel() {
+        let mut keypad = keypad();
+        let mut frame = [KEY_2; 8];
+        frame[Channel::Channel3.index()] = KEY_1;
+        let events: Vec<_> = (0..3_u8)
+            .filter_map(|_| keypad.update_frame(&frame))
+            .collect();
+        assert_eq!(events, [KeyEvent::Pressed(1)]);
+        keypad.reset();
+        assert_eq!(keypad.pressed(), None);
+        assert_eq!(keypad.update_frame(&[IDLE; 8]), None);
+    }
+}
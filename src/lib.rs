@@ -134,7 +134,7 @@
 #![deny(clippy::str_to_string)]
 #![deny(clippy::string_add)]
 #![deny(clippy::string_slice)]
-#![deny(clippy::string_to_string)]
+// #![deny(clippy::string_to_string)] // removed in 1.91.0, covered by clippy::implicit_clone
 #![deny(clippy::suspicious_xor_used_as_pow)]
 //#![deny(clippy::tests_outside_test_module)] // coming in 1.70.0
 #![deny(clippy::todo)]
@@ -153,94 +153,248 @@
 #![deny(clippy::verbose_file_reads)]
 #![deny(clippy::wildcard_enum_match_arm)]
 
+#[cfg(any(test, feature = "cli"))]
+extern crate alloc;
+
 pub mod channel;
 pub mod chip_definitions;
 mod chip_interface;
+#[cfg(any(test, feature = "cli"))]
+pub mod cli;
+pub mod correction;
+pub mod decoder;
+pub mod diagnostics;
 pub mod error;
+pub mod excitation;
+pub mod filter;
+pub mod keypad;
+pub mod lookup;
+pub mod ntc;
+pub mod rail;
+pub mod recorder;
+pub mod sensor;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod statistics;
+pub mod thermocouple;
+pub mod threshold;
+pub mod transport;
+pub mod virtual_channel;
 
 use crate::{
-    channel::Channel,
+    channel::{Channel, ChannelFlags},
     chip_definitions::{
-        GeneralConfigFlags, Oversampling, SamplingRate, SequenceConfig, SystemStatusFlags,
+        GeneralConfigFlags, Oversampling, PinMode, RegisterAddress, Resolution, SamplingRate,
+        SequenceConfig, SystemStatusFlags,
     },
     chip_interface::ChipInterface,
+    diagnostics::InputCheck,
     error::Error,
+    excitation::{Bridge, BridgeReading, Excitation},
+    filter::FrameProcessor,
+    sensor::{ChannelModels, Reading},
+    transport::{I2cTransport, SpiTransport, Transport},
+    virtual_channel::{VirtualChannel, VirtualFrame},
+};
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{I2c, SevenBitAddress},
+    spi::SpiDevice,
 };
-use embedded_hal::i2c::{I2c, SevenBitAddress};
 
-pub struct Tla2528<I2C> {
-    chip: ChipInterface<I2C>,
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Tla2528<T> {
+    chip: ChipInterface<T>,
 }
 
-impl<I2C> Tla2528<I2C>
+/// The SPI sibling of the TLA2528, sharing its register map. The ADS7038 is driven the same way.
+pub type Tla2518<SPI> = Tla2528<SpiTransport<SPI>>;
+
+#[allow(
+    clippy::multiple_inherent_impl,
+    reason = "Constructing over I2C and high-speed mode need the I2c bound."
+)]
+impl<I2C> Tla2528<I2cTransport<I2C>>
 where
     I2C: I2c<SevenBitAddress>,
-    I2C::Error: Into<Error<I2C::Error>>,
 {
     pub fn new(i2c: I2C, address: u8) -> Self {
+        Tla2528::with_transport(I2cTransport::new(i2c, address))
+    }
+
+    /// Reports whether the device is currently in I2C high-speed mode (up to 3.4 MHz).
+    ///
+    /// The driver cannot enter high-speed mode, nor re-enter it after a STOP or a brownout. The
+    /// device leaves high-speed mode at every STOP condition, and every `embedded_hal::i2c::I2c`
+    /// call ends with one, so the HS-mode master code and the repeated START that must follow it
+    /// cannot be sent through that trait. Entering high-speed mode is therefore the HAL's
+    /// responsibility: it must send the master code and a repeated START ahead of every
+    /// transaction. Called through such a HAL, this confirms that the device followed the
+    /// switch.
+    ///
+    /// # Errors
+    ///
+    /// Passes out I2C communication errors.
+    pub fn is_high_speed_mode(&mut self) -> Result<bool, Error<I2C::Error>> {
+        Ok(self
+            .chip
+            .read_system_status()?
+            .contains(SystemStatusFlags::I2C_HIGH_SPEED))
+    }
+}
+
+#[allow(
+    clippy::multiple_inherent_impl,
+    reason = "Constructing over SPI needs the SpiDevice bound."
+)]
+impl<SPI> Tla2528<SpiTransport<SPI>>
+where
+    SPI: SpiDevice,
+{
+    /// Creates a driver for a TLA2518 or ADS7038 on its own SPI chip select. The name differs
+    /// from `new()` so that calls through `Tla2528` remain unambiguous.
+    pub fn new_spi(spi: SPI) -> Self {
+        Tla2528::with_transport(SpiTransport::new(spi))
+    }
+}
+
+impl<T> Tla2528<T>
+where
+    T: Transport,
+{
+    /// Creates a driver for a device reached through `transport`.
+    pub fn with_transport(transport: T) -> Self {
         Tla2528 {
-            chip: ChipInterface::new(i2c, address),
+            chip: ChipInterface::new(transport),
         }
     }
+
     /// # Errors
-    /// Passes on I2C errors found in `single_register_read()`
+    /// Passes on bus errors found in `single_register_read()`
     ///
-    /// Passes out I2C communication errors.
-    pub fn get_system_status(&mut self) -> Result<SystemStatusFlags, Error<I2C::Error>> {
+    /// Passes out bus communication errors.
+    pub fn get_system_status(&mut self) -> Result<SystemStatusFlags, Error<T::Error>> {
         self.chip.read_system_status()
     }
 
+    /// Runs the ADC's internal offset calibration. Gain and offset errors of the signal chain
+    /// are corrected with a `correction::CorrectionTable`.
+    ///
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn calibrate(&mut self) -> Result<(), Error<I2C::Error>> {
+    /// Returns `Error::CalibrationTimeout` if the device does not clear the calibration bit.
+    ///
+    /// Passes out bus communication errors.
+    pub fn calibrate(&mut self) -> Result<(), Error<T::Error>> {
+        const MAX_CALIBRATION_POLLS: usize = 1024;
         self.chip
             .write_general_config(GeneralConfigFlags::CALIBRATE_ADC_OFFSET)?;
 
-        while self
-            .chip
-            .read_general_config()?
-            .contains(GeneralConfigFlags::CALIBRATE_ADC_OFFSET)
-        {
-            // Intentionally empty
+        for _ in 0..MAX_CALIBRATION_POLLS {
+            if !self
+                .chip
+                .read_general_config()?
+                .contains(GeneralConfigFlags::CALIBRATE_ADC_OFFSET)
+            {
+                return Ok(());
+            }
         }
-        Ok(())
+        Err(Error::CalibrationTimeout)
     }
 
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn set_oversampling_ratio(&mut self, ratio: Oversampling) -> Result<(), Error<I2C::Error>> {
+    /// Passes out bus communication errors.
+    pub fn set_oversampling_ratio(&mut self, ratio: Oversampling) -> Result<(), Error<T::Error>> {
         self.chip.configure_oversampling(ratio)
     }
 
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn set_sampling_rate(&mut self, rate: SamplingRate) -> Result<(), Error<I2C::Error>> {
+    /// Passes out bus communication errors.
+    pub fn set_sampling_rate(&mut self, rate: SamplingRate) -> Result<(), Error<T::Error>> {
         self.chip.configure_sampling_rate(rate)
     }
 
+    /// Reads a register directly, for diagnostics.
+    ///
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn prepare_for_auto_sequence_mode(&mut self) -> Result<(), Error<I2C::Error>> {
+    /// Passes out bus communication errors.
+    pub fn read_register(&mut self, register: RegisterAddress) -> Result<u8, Error<T::Error>> {
+        self.chip.register_read(register)
+    }
+
+    /// Writes a register directly, for diagnostics. Settings written this way may be
+    /// overwritten by the other configuration methods.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus communication errors.
+    pub fn write_register(
+        &mut self,
+        register: RegisterAddress,
+        value: u8,
+    ) -> Result<(), Error<T::Error>> {
+        self.chip.register_write(register, value)
+    }
+
+    /// Sets the function of one pin. `prepare_for_auto_sequence_mode()` and
+    /// `prepare_for_manual_mode()` return all pins to analog inputs.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus communication errors.
+    pub fn configure_pin(
+        &mut self,
+        channel: Channel,
+        mode: PinMode,
+    ) -> Result<(), Error<T::Error>> {
+        self.chip.configure_pin(channel, mode)
+    }
+
+    /// Sets the level of a pin configured as a digital output.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus communication errors.
+    pub fn set_output(&mut self, channel: Channel, high: bool) -> Result<(), Error<T::Error>> {
+        self.chip.write_output(channel, high)
+    }
+
+    /// Reads the level of a pin configured as a digital input or output.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus communication errors.
+    pub fn read_input(&mut self, channel: Channel) -> Result<bool, Error<T::Error>> {
+        self.chip.read_input(channel)
+    }
+
+    /// # Errors
+    ///
+    /// Passes out bus communication errors.
+    pub fn prepare_for_auto_sequence_mode(&mut self) -> Result<(), Error<T::Error>> {
         self.chip.configure_all_pins_as_analog_inputs()?;
         self.chip.configure_auto_sequence_mode()
     }
 
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn prepare_for_manual_mode(&mut self) -> Result<(), Error<I2C::Error>> {
+    /// Passes out bus communication errors.
+    pub fn prepare_for_manual_mode(&mut self) -> Result<(), Error<T::Error>> {
         self.chip.configure_all_pins_as_analog_inputs()?;
         self.chip.configure_manual_mode()
     }
 
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn acquire_data(&mut self) -> Result<[u16; 8], Error<I2C::Error>> {
+    /// Returns `Error::InvalidChannelAddress` or `Error::DataItemsMisOrdered` if the channel IDs
+    /// in the sequence do not run from `Channel0` to `Channel7`.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_data(&mut self) -> Result<[u16; 8], Error<T::Error>> {
         // Enable channel sequencing SEQ_START = 1
         self.chip
             .write_sequence_config(SequenceConfig::StartedAuto)?;
@@ -258,10 +412,289 @@ where
         Ok(data)
     }
 
+    /// Acquires one auto-sequence frame containing only `channels`. Channels not sampled are
+    /// `None`.
+    ///
+    /// The device must be prepared with `prepare_for_auto_sequence_mode()`; the sequence is
+    /// restored to all channels afterwards.
+    ///
     /// # Errors
     ///
-    /// Passes out I2C communication errors.
-    pub fn acquire_channel_data(&mut self, channel: Channel) -> Result<u16, Error<I2C::Error>> {
+    /// Returns `Error::InvalidChannelAddress` or `Error::DataItemsMisOrdered` if the channel IDs
+    /// in the sequence do not match `channels`.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_sequence(
+        &mut self,
+        channels: ChannelFlags,
+    ) -> Result<[Option<u16>; 8], Error<T::Error>> {
+        if channels.is_empty() {
+            return Ok([None; 8]);
+        }
+        self.chip.set_auto_sequence_channels(channels)?;
+        self.chip
+            .write_sequence_config(SequenceConfig::StartedAuto)?;
+        let data = self.chip.data_read_sequence(channels);
+        self.chip
+            .write_sequence_config(SequenceConfig::StoppedAuto)?;
+        self.chip.set_auto_sequence_channels(ChannelFlags::all())?;
+        data
+    }
+
+    /// Acquires the channels needed by `virtual_channels`, and any further `physical` channels,
+    /// in one auto-sequence frame and computes the virtual values.
+    ///
+    /// # Errors
+    ///
+    /// As for `acquire_sequence()`.
+    pub fn acquire_virtual<const N: usize>(
+        &mut self,
+        virtual_channels: &[VirtualChannel; N],
+        physical: ChannelFlags,
+    ) -> Result<VirtualFrame<N>, Error<T::Error>> {
+        let channels = virtual_channels
+            .iter()
+            .fold(physical, |channels, virtual_channel| {
+                channels | virtual_channel.channels()
+            });
+        let data = self.acquire_sequence(channels)?;
+        let mut virtual_values = [None; N];
+        for (value, virtual_channel) in virtual_values.iter_mut().zip(virtual_channels) {
+            *value = virtual_channel.evaluate(&data);
+        }
+        Ok(VirtualFrame {
+            physical: data,
+            virtual_values,
+        })
+    }
+
+    /// Acquires a frame as `acquire_data()` does and passes it through `processor`, such as a
+    /// `filter::FilterBank`, before returning it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` or `Error::DataItemsMisOrdered` if the channel IDs
+    /// in the sequence do not run from `Channel0` to `Channel7`.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_processed_data<P>(
+        &mut self,
+        processor: &mut P,
+    ) -> Result<[u16; 8], Error<T::Error>>
+    where
+        P: FrameProcessor,
+    {
+        let mut data = self.acquire_data()?;
+        processor.process_frame(&mut data);
+        Ok(data)
+    }
+
+    /// Acquires a frame as `acquire_data()` does and converts each channel with the model
+    /// attached to it. Channels without a model are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` or `Error::DataItemsMisOrdered` if the channel IDs
+    /// in the sequence do not run from `Channel0` to `Channel7`.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_readings(
+        &mut self,
+        models: &ChannelModels<'_>,
+    ) -> Result<[Option<Reading>; 8], Error<T::Error>> {
+        let data = self.acquire_data()?;
+        Ok(models.convert_frame(&data))
+    }
+
+    /// Powers a sensor from `excitation.pin`, waits for it to settle and samples `channels`,
+    /// then restores the pin configuration. Channels not sampled, including the excitation pin
+    /// itself, are `None`.
+    ///
+    /// The device must be in manual mode. The pin is driven high as a push-pull output while
+    /// sampling. Restoring its previous configuration and output level de-energises the sensor
+    /// unless the pin was already driving it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` or `Error::IncorrectChannelAddress` if a sampled
+    /// channel is not returned correctly. The pin configuration is restored even when sampling
+    /// fails.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_excited<D>(
+        &mut self,
+        excitation: Excitation,
+        channels: ChannelFlags,
+        delay: &mut D,
+    ) -> Result<[Option<u16>; 8], Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        let snapshot = self.chip.save_pin_config()?;
+        let sampled = self.excite_and_sample(excitation, channels, delay);
+        let restored = self.chip.restore_pin_config(snapshot);
+        let data = sampled?;
+        restored?;
+        Ok(data)
+    }
+
+    fn excite_and_sample<D>(
+        &mut self,
+        excitation: Excitation,
+        channels: ChannelFlags,
+        delay: &mut D,
+    ) -> Result<[Option<u16>; 8], Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        // The level is written first so the pin does not briefly drive a stale one
+        self.chip.write_output(excitation.pin, true)?;
+        self.chip
+            .configure_pin(excitation.pin, PinMode::DigitalOutputPushPull)?;
+        delay.delay_ns(excitation.settle_ns);
+        self.acquire_channels(channels - ChannelFlags::from(excitation.pin))
+    }
+
+    /// Measures a bridge with forward and then reverse excitation, waiting `settle_ns` after
+    /// each polarity change, and combines the phases into an offset-cancelled reading. The
+    /// configuration of the excitation pins is restored afterwards.
+    ///
+    /// The device must be in manual mode. Both excitation pins are driven low before they are
+    /// switched to push-pull outputs, so levels left in `GPO_VALUE` cannot excite the bridge.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` or `Error::IncorrectChannelAddress` if a sense
+    /// channel is not returned correctly. The pin configuration is restored even when sampling
+    /// fails.
+    ///
+    /// Passes out bus communication errors.
+    pub fn measure_bridge<D>(
+        &mut self,
+        bridge: &Bridge,
+        delay: &mut D,
+    ) -> Result<BridgeReading, Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        let snapshot = self.chip.save_pin_config()?;
+        let phases = self.measure_bridge_phases(bridge, delay);
+        let restored = self.chip.restore_pin_config(snapshot);
+        let (forward, reverse) = phases?;
+        restored?;
+        Ok(bridge.combine(forward, reverse))
+    }
+
+    fn measure_bridge_phases<D>(
+        &mut self,
+        bridge: &Bridge,
+        delay: &mut D,
+    ) -> Result<(i32, i32), Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        for pin in [bridge.excitation_positive, bridge.excitation_negative] {
+            self.chip.write_output(pin, false)?;
+            self.chip
+                .configure_pin(pin, PinMode::DigitalOutputPushPull)?;
+        }
+        let mut differential = [0_i32; 2];
+        let phases = [
+            (bridge.excitation_negative, bridge.excitation_positive),
+            (bridge.excitation_positive, bridge.excitation_negative),
+        ];
+        for (result, (low, high)) in differential.iter_mut().zip(phases) {
+            self.chip.write_output(low, false)?;
+            self.chip.write_output(high, true)?;
+            delay.delay_ns(bridge.settle_ns);
+            let positive = self.acquire_channel_data(bridge.sense_positive)?;
+            let negative = self.acquire_channel_data(bridge.sense_negative)?;
+            *result = i32::from(positive) - i32::from(negative);
+        }
+        let [forward, reverse] = differential;
+        Ok((forward, reverse))
+    }
+
+    /// Checks whether `channel` is connected, open or shorted by driving it high and then low
+    /// for `charge_ns` as a push-pull output, sampling it as an analog input after each, and
+    /// classifying the results with `InputCheck::classify()`. The pin configuration is restored
+    /// afterwards.
+    ///
+    /// The device must be in manual mode. The check briefly drives the pin, so it must only be
+    /// used on inputs whose source tolerates that.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` or `Error::IncorrectChannelAddress` if the
+    /// channel is not returned correctly. The pin configuration is restored even when sampling
+    /// fails.
+    ///
+    /// Passes out bus communication errors.
+    pub fn check_input<D>(
+        &mut self,
+        channel: Channel,
+        charge_ns: u32,
+        delay: &mut D,
+    ) -> Result<InputCheck, Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        let resolution = self
+            .chip
+            .read_oversampling()?
+            .map_or(Resolution::Bits12, Oversampling::resolution);
+        let snapshot = self.chip.save_pin_config()?;
+        let samples = self
+            .sample_after_drive(channel, true, charge_ns, delay)
+            .and_then(|after_high| {
+                let after_low = self.sample_after_drive(channel, false, charge_ns, delay)?;
+                Ok((after_high, after_low))
+            });
+        let restored = self.chip.restore_pin_config(snapshot);
+        let (after_high, after_low) = samples?;
+        restored?;
+        Ok(InputCheck::classify(after_high, after_low, resolution))
+    }
+
+    fn sample_after_drive<D>(
+        &mut self,
+        channel: Channel,
+        high: bool,
+        charge_ns: u32,
+        delay: &mut D,
+    ) -> Result<u16, Error<T::Error>>
+    where
+        D: DelayNs,
+    {
+        self.chip.write_output(channel, high)?;
+        self.chip
+            .configure_pin(channel, PinMode::DigitalOutputPushPull)?;
+        delay.delay_ns(charge_ns);
+        self.chip.configure_pin(channel, PinMode::AnalogInput)?;
+        self.acquire_channel_data(channel)
+    }
+
+    fn acquire_channels(
+        &mut self,
+        channels: ChannelFlags,
+    ) -> Result<[Option<u16>; 8], Error<T::Error>> {
+        let mut data = [None; 8];
+        for channel in channels.channels() {
+            let value = self.acquire_channel_data(channel)?;
+            if let Some(slot) = data.get_mut(channel.index()) {
+                *slot = Some(value);
+            }
+        }
+        Ok(data)
+    }
+
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChannelAddress` if a frame carries an invalid channel ID, or
+    /// `Error::IncorrectChannelAddress` if the requested channel is not returned.
+    ///
+    /// Passes out bus communication errors.
+    pub fn acquire_channel_data(&mut self, channel: Channel) -> Result<u16, Error<T::Error>> {
         self.chip.set_channel(channel)?;
         match self.chip.data_channel_read(channel) {
             Ok((data, _)) => Ok(data),
@@ -269,3 +702,578 @@ where
         }
     }
 }
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use crate::{
+        channel::{Channel, ChannelFlags},
+        chip_definitions::{
+            GeneralConfigFlags, OpCode, Oversampling, PinMode, RegisterAddress, Resolution,
+            SequenceConfig, SystemStatusFlags,
+        },
+        diagnostics::{InputCheck, InputState},
+        excitation::{Bridge, BridgeReading, Excitation},
+        recorder::{Record, Recorder, Records},
+        sim::SimulatedTla2528,
+        transport::I2cTransport,
+        virtual_channel::{VirtualChannel, VirtualFrame},
+        Tla2528,
+    };
+    use alloc::vec::Vec;
+    use embedded_hal::{
+        delay::DelayNs,
+        i2c::{ErrorKind, I2c},
+    };
+
+    const ADDRESS: u8 = 0x10;
+
+    /// The registers that make up a pin's configuration, in the order `PinRegisters` holds
+    /// them.
+    const PIN_REGISTERS: [RegisterAddress; 4] = [
+        RegisterAddress::PinConfig,
+        RegisterAddress::GpioConfig,
+        RegisterAddress::GpioDriveConfig,
+        RegisterAddress::GpOutValue,
+    ];
+
+    type PinRegisters = [u8; 4];
+
+    /// A delay that only adds up the time requested.
+    #[derive(Default)]
+    struct TotalDelay {
+        ns: u64,
+    }
+
+    #[allow(
+        clippy::missing_trait_methods,
+        reason = "The provided methods are built on delay_ns()."
+    )]
+    impl DelayNs for TotalDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.ns += u64::from(ns);
+        }
+    }
+
+    fn pin_registers<I2C: I2c>(adc: &mut Tla2528<I2cTransport<I2C>>) -> PinRegisters {
+        PIN_REGISTERS.map(|register| adc.read_register(register).unwrap())
+    }
+
+    /// Replays the pin register writes of a recorded log onto `registers` and returns, after
+    /// each write, the level `channel` drives as a push-pull output, or `None` while it does
+    /// not drive one. Repeated states are dropped.
+    fn driven_levels(
+        log: &[u8],
+        mut registers: PinRegisters,
+        channel: Channel,
+    ) -> Vec<Option<bool>> {
+        let mask = 1 << channel as u8;
+        let mut levels = Vec::from([None]);
+        for record in Records::new(log) {
+            let Record::Write {
+                data: &[opcode, register, value, ..],
+                ..
+            } = record
+            else {
+                continue;
+            };
+            let Some(slot) = PIN_REGISTERS
+                .iter()
+                .position(|pin_register| pin_register.value() == register)
+                .and_then(|index| registers.get_mut(index))
+            else {
+                continue;
+            };
+            match OpCode::try_from(opcode) {
+                Ok(OpCode::SingleRegisterWrite) => *slot = value,
+                Ok(OpCode::SetBit) => *slot |= value,
+                Ok(OpCode::ClearBit) => *slot &= !value,
+                _ => continue,
+            }
+            let [pin, gpio, drive, output] = registers;
+            let level = (pin & gpio & drive & mask != 0).then_some(output & mask != 0);
+            if levels.last() != Some(&level) {
+                levels.push(level);
+            }
+        }
+        levels
+    }
+    const CODES: [u16; 8] = [0x000, 0x123, 0x246, 0x369, 0x48C, 0x5AF, 0x6D2, 0xFFF];
+
+    fn simulated<'a>() -> SimulatedTla2528<'a> {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        for (channel, code) in Channel::ALL.into_iter().zip(CODES) {
+            sim.set_input(channel, code);
+        }
+        sim
+    }
+
+    #[test]
+    fn reports_power_on_status() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        let status = adc.get_system_status().unwrap();
+        assert!(status.contains(SystemStatusFlags::BROWNOUT_RESET));
+        assert!(!status.contains(SystemStatusFlags::I2C_HIGH_SPEED));
+    }
+
+    #[test]
+    fn auto_sequence_without_oversampling() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        assert_eq!(adc.acquire_data().unwrap(), CODES);
+        assert_eq!(
+            adc.acquire_sequence(ChannelFlags::CHANNEL1 | ChannelFlags::CHANNEL7)
+                .unwrap(),
+            [None, Some(0x123), None, None, None, None, None, Some(0xFFF)]
+        );
+    }
+
+    #[test]
+    fn auto_sequence_with_oversampling() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio16).unwrap();
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        assert_eq!(adc.acquire_data().unwrap(), CODES.map(|code| code << 4_u32));
+        assert_eq!(
+            adc.acquire_sequence(ChannelFlags::CHANNEL2).unwrap(),
+            [None, None, Some(0x2460), None, None, None, None, None]
+        );
+    }
+
+    #[test]
+    fn manual_mode_without_oversampling() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        for (channel, code) in Channel::ALL.into_iter().zip(CODES) {
+            assert_eq!(adc.acquire_channel_data(channel).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn manual_mode_with_oversampling() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio2).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        for (channel, code) in Channel::ALL.into_iter().zip(CODES) {
+            assert_eq!(adc.acquire_channel_data(channel).unwrap(), code << 4_u32);
+        }
+    }
+
+    #[test]
+    fn oversampling_written_through_registers_is_followed() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        adc.write_register(RegisterAddress::OsrConfig, 0x03)
+            .unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x3690);
+        adc.write_register(RegisterAddress::OsrConfig, 0x00)
+            .unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x369);
+    }
+
+    #[test]
+    fn gpio_outputs_and_inputs() {
+        let mut sim = simulated();
+        sim.set_digital_input(Channel::Channel5, true);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.configure_pin(Channel::Channel1, PinMode::DigitalOutputPushPull)
+            .unwrap();
+        adc.configure_pin(Channel::Channel2, PinMode::DigitalOutputOpenDrain)
+            .unwrap();
+        adc.configure_pin(Channel::Channel5, PinMode::DigitalInput)
+            .unwrap();
+        adc.set_output(Channel::Channel1, true).unwrap();
+        assert!(adc.read_input(Channel::Channel5).unwrap());
+        assert!(!adc.read_input(Channel::Channel6).unwrap());
+        assert_eq!(
+            adc.read_register(RegisterAddress::GpioDriveConfig).unwrap(),
+            0b_0000_0010
+        );
+
+        assert_eq!(sim.output_level(Channel::Channel1), Some(true));
+        assert_eq!(sim.output_level(Channel::Channel2), Some(false));
+        assert_eq!(sim.output_level(Channel::Channel5), None);
+    }
+
+    #[test]
+    fn pins_return_to_analog_inputs() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.configure_pin(Channel::Channel4, PinMode::DigitalOutputPushPull)
+            .unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        assert_eq!(adc.read_register(RegisterAddress::PinConfig).unwrap(), 0);
+        assert_eq!(adc.acquire_channel_data(Channel::Channel4).unwrap(), 0x48C);
+    }
+
+    #[test]
+    fn calibration_sets_and_waits_for_the_general_config_bit() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        let data_config = adc.read_register(RegisterAddress::DataConfig).unwrap();
+        adc.calibrate().unwrap();
+        assert_eq!(
+            adc.read_register(RegisterAddress::DataConfig).unwrap(),
+            data_config
+        );
+        let general = GeneralConfigFlags::from_bits_retain(
+            adc.read_register(RegisterAddress::GeneralConfig).unwrap(),
+        );
+        assert!(!general.contains(GeneralConfigFlags::CALIBRATE_ADC_OFFSET));
+    }
+
+    #[test]
+    fn high_speed_mode_follows_the_host() {
+        let mut sim = simulated();
+        assert!(!Tla2528::new(&mut sim, ADDRESS)
+            .is_high_speed_mode()
+            .unwrap());
+        sim.set_high_speed_host(true);
+        assert!(Tla2528::new(&mut sim, ADDRESS)
+            .is_high_speed_mode()
+            .unwrap());
+    }
+
+    const EXCITATION: Excitation = Excitation {
+        pin: Channel::Channel5,
+        settle_ns: 250_000,
+    };
+
+    #[test]
+    fn excited_acquisition_samples_while_the_pin_is_high() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        adc.configure_pin(Channel::Channel7, PinMode::DigitalOutputOpenDrain)
+            .unwrap();
+        adc.set_output(Channel::Channel7, true).unwrap();
+        let before = pin_registers(&mut adc);
+        let mut delay = TotalDelay::default();
+        let channels = ChannelFlags::CHANNEL1 | ChannelFlags::CHANNEL3 | ChannelFlags::CHANNEL5;
+        assert_eq!(
+            adc.acquire_excited(EXCITATION, channels, &mut delay)
+                .unwrap(),
+            [None, Some(0x123), None, Some(0x369), None, None, None, None]
+        );
+        assert_eq!(delay.ns, 250_000);
+        assert_eq!(pin_registers(&mut adc), before);
+        assert_eq!(sim.output_level(Channel::Channel5), None);
+    }
+
+    #[test]
+    fn excitation_pin_is_driven_high_before_it_becomes_an_output() {
+        let mut sim = simulated();
+        Tla2528::new(&mut sim, ADDRESS)
+            .prepare_for_manual_mode()
+            .unwrap();
+        let before = PIN_REGISTERS.map(|register| sim.register(register));
+        let mut buffer = [0_u8; 1024];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        Tla2528::new(&mut recorder, ADDRESS)
+            .acquire_excited(
+                EXCITATION,
+                ChannelFlags::CHANNEL0,
+                &mut TotalDelay::default(),
+            )
+            .unwrap();
+        assert!(!recorder.is_truncated());
+        // Restoring the output level ahead of the pin function drives the pin low before it
+        // is released
+        assert_eq!(
+            driven_levels(recorder.log(), before, Channel::Channel5),
+            [None, Some(true), Some(false), None]
+        );
+    }
+
+    #[test]
+    fn excitation_pin_is_restored_when_sampling_fails() {
+        let mut sim = simulated();
+        let before = {
+            let mut adc = Tla2528::new(&mut sim, ADDRESS);
+            adc.prepare_for_manual_mode().unwrap();
+            pin_registers(&mut adc)
+        };
+        // Fail the conversion read, after the snapshot, the excitation and the channel select
+        sim.inject_bus_error(ErrorKind::Other, 9, 1);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        let result = adc.acquire_excited(
+            EXCITATION,
+            ChannelFlags::CHANNEL0,
+            &mut TotalDelay::default(),
+        );
+        assert_eq!(
+            result.unwrap_err().operation(),
+            Some(crate::error::Operation::DataRead)
+        );
+        assert_eq!(pin_registers(&mut adc), before);
+        assert_eq!(sim.output_level(Channel::Channel5), None);
+    }
+
+    const BRIDGE: Bridge = Bridge {
+        excitation_positive: Channel::Channel6,
+        excitation_negative: Channel::Channel7,
+        sense_positive: Channel::Channel2,
+        sense_negative: Channel::Channel3,
+        settle_ns: 100_000,
+        resolution: Resolution::Bits12,
+    };
+
+    /// A bridge output of 100 mV that follows the excitation polarity, on top of a 10 mV
+    /// offset that does not, as the positive sense input sees it in the forward and then the
+    /// reverse phase.
+    fn bridge_output() -> impl FnMut(u64) -> u32 {
+        let mut phase = 0_u8;
+        move |_| {
+            phase += 1;
+            if phase == 1 {
+                2_110_000
+            } else {
+                1_910_000
+            }
+        }
+    }
+
+    /// Leaves both excitation pins as analog inputs with their `GPO_VALUE` bits set.
+    fn prepare_bridge<I2C: I2c>(adc: &mut Tla2528<I2cTransport<I2C>>) {
+        adc.prepare_for_manual_mode().unwrap();
+        adc.set_output(BRIDGE.excitation_positive, true).unwrap();
+        adc.set_output(BRIDGE.excitation_negative, true).unwrap();
+    }
+
+    #[test]
+    fn bridge_phases_cancel_the_offset() {
+        let mut source = bridge_output();
+        let mut sim = simulated();
+        sim.set_avdd(4_096_000);
+        sim.attach_source(Channel::Channel2, &mut source);
+        sim.set_input(Channel::Channel3, 2000);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        prepare_bridge(&mut adc);
+        let before = pin_registers(&mut adc);
+        let mut delay = TotalDelay::default();
+        assert_eq!(
+            adc.measure_bridge(&BRIDGE, &mut delay).unwrap(),
+            BridgeReading {
+                differential: 100,
+                offset: 10,
+                microvolts_per_volt: 24_414,
+            }
+        );
+        assert_eq!(delay.ns, 200_000);
+        assert_eq!(pin_registers(&mut adc), before);
+    }
+
+    #[test]
+    fn bridge_pins_are_driven_low_before_they_become_outputs() {
+        let mut sim = simulated();
+        prepare_bridge(&mut Tla2528::new(&mut sim, ADDRESS));
+        let before = PIN_REGISTERS.map(|register| sim.register(register));
+        let mut buffer = [0_u8; 2048];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        Tla2528::new(&mut recorder, ADDRESS)
+            .measure_bridge(&BRIDGE, &mut TotalDelay::default())
+            .unwrap();
+        assert!(!recorder.is_truncated());
+        for pin in [BRIDGE.excitation_positive, BRIDGE.excitation_negative] {
+            let levels = driven_levels(recorder.log(), before, pin);
+            // The set GPO_VALUE bits are never driven before the phases start
+            assert_eq!(levels.get(..2), Some(&[None, Some(false)][..]), "{pin:?}");
+            assert_eq!(levels.last(), Some(&None), "{pin:?}");
+        }
+    }
+
+    #[test]
+    fn bridge_pins_are_restored_when_sampling_fails() {
+        let mut sim = simulated();
+        let before = {
+            let mut adc = Tla2528::new(&mut sim, ADDRESS);
+            prepare_bridge(&mut adc);
+            pin_registers(&mut adc)
+        };
+        // Fail the first conversion read, after the snapshot, the pin setup, the first
+        // polarity and the channel select
+        sim.inject_bus_error(ErrorKind::Other, 15, 1);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        let result = adc.measure_bridge(&BRIDGE, &mut TotalDelay::default());
+        assert_eq!(
+            result.unwrap_err().operation(),
+            Some(crate::error::Operation::DataRead)
+        );
+        assert_eq!(pin_registers(&mut adc), before);
+    }
+
+    const VIRTUAL_CHANNELS: [VirtualChannel; 3] = [
+        VirtualChannel::Difference {
+            positive: Channel::Channel2,
+            negative: Channel::Channel1,
+        },
+        VirtualChannel::Ratio {
+            signal: Channel::Channel1,
+            reference: Channel::Channel7,
+        },
+        VirtualChannel::Ratio {
+            signal: Channel::Channel3,
+            reference: Channel::Channel0,
+        },
+    ];
+
+    #[test]
+    fn virtual_channels_are_computed_from_one_frame() {
+        let mut sim = simulated();
+        Tla2528::new(&mut sim, ADDRESS)
+            .prepare_for_auto_sequence_mode()
+            .unwrap();
+        let start_ns = sim.time_ns();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        assert_eq!(
+            adc.acquire_virtual(&VIRTUAL_CHANNELS, ChannelFlags::CHANNEL5)
+                .unwrap(),
+            VirtualFrame {
+                physical: [
+                    Some(0x000),
+                    Some(0x123),
+                    Some(0x246),
+                    Some(0x369),
+                    None,
+                    Some(0x5AF),
+                    None,
+                    Some(0xFFF)
+                ],
+                // 0x123 / 0xFFF in millionths, and no ratio against a reference reading 0
+                virtual_values: [Some(0x0123_i32), Some(71_062_i32), None],
+            }
+        );
+        // Only the six channels involved were converted
+        assert_eq!(sim.time_ns() - start_ns, 6_000);
+    }
+
+    #[test]
+    fn virtual_acquisition_restores_the_sequencer_and_leaves_pins_alone() {
+        let mut sim = simulated();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio4).unwrap();
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        adc.configure_pin(Channel::Channel4, PinMode::DigitalOutputPushPull)
+            .unwrap();
+        adc.set_output(Channel::Channel4, true).unwrap();
+        let before = pin_registers(&mut adc);
+        let [difference, ..] = VIRTUAL_CHANNELS;
+        let frame = adc
+            .acquire_virtual(&[difference], ChannelFlags::empty())
+            .unwrap();
+        assert_eq!(frame.virtual_values, [Some(0x1230_i32)]);
+        assert_eq!(
+            frame.physical,
+            [
+                None,
+                Some(0x1230),
+                Some(0x2460),
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        assert_eq!(pin_registers(&mut adc), before);
+        assert_eq!(
+            adc.read_register(RegisterAddress::AutoSequenceChannelSelect)
+                .unwrap(),
+            0xFF
+        );
+        assert_eq!(
+            adc.read_register(RegisterAddress::SequenceConfig).unwrap(),
+            SequenceConfig::StoppedAuto.value()
+        );
+        assert_eq!(sim.output_level(Channel::Channel4), Some(true));
+    }
+
+    /// Inputs that are open, shorted to AVDD, shorted to ground and driven by a source.
+    fn simulated_input_faults<'a>() -> SimulatedTla2528<'a> {
+        let mut sim = simulated();
+        sim.set_floating(Channel::Channel1);
+        sim.set_input(Channel::Channel2, 0xFFF);
+        sim.set_input(Channel::Channel3, 0x000);
+        sim.set_input(Channel::Channel4, 0x800);
+        sim
+    }
+
+    #[test]
+    fn input_checks_classify_each_input() {
+        let mut sim = simulated_input_faults();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        adc.configure_pin(Channel::Channel6, PinMode::DigitalOutputOpenDrain)
+            .unwrap();
+        adc.set_output(Channel::Channel4, true).unwrap();
+        let before = pin_registers(&mut adc);
+        for (channel, state, after_high, after_low) in [
+            (Channel::Channel1, InputState::Open, 0xFFF, 0x000),
+            (Channel::Channel2, InputState::ShortedToSupply, 0xFFF, 0xFFF),
+            (Channel::Channel3, InputState::ShortedToGround, 0x000, 0x000),
+            (Channel::Channel4, InputState::Connected, 0x800, 0x800),
+        ] {
+            let mut delay = TotalDelay::default();
+            assert_eq!(
+                adc.check_input(channel, 5_000, &mut delay).unwrap(),
+                InputCheck {
+                    state,
+                    after_high,
+                    after_low
+                },
+                "{channel:?}"
+            );
+            assert_eq!(delay.ns, 10_000);
+            assert_eq!(pin_registers(&mut adc), before, "{channel:?}");
+        }
+    }
+
+    #[test]
+    fn input_checks_follow_the_oversampled_resolution() {
+        let mut sim = simulated_input_faults();
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio2).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        let shorted = adc
+            .check_input(Channel::Channel3, 5_000, &mut TotalDelay::default())
+            .unwrap();
+        assert_eq!(shorted.state, InputState::ShortedToGround);
+        let open = adc
+            .check_input(Channel::Channel1, 5_000, &mut TotalDelay::default())
+            .unwrap();
+        assert_eq!((open.state, open.after_high), (InputState::Open, 0xFFF0));
+    }
+
+    #[test]
+    fn input_check_restores_the_pin_when_sampling_fails() {
+        let mut sim = simulated_input_faults();
+        let before = {
+            let mut adc = Tla2528::new(&mut sim, ADDRESS);
+            adc.prepare_for_manual_mode().unwrap();
+            adc.configure_pin(Channel::Channel1, PinMode::DigitalInput)
+                .unwrap();
+            pin_registers(&mut adc)
+        };
+        // Fail the first conversion read, after the oversampling read, the snapshot, driving
+        // the pin high, switching it back and the channel select
+        sim.inject_bus_error(ErrorKind::Other, 11, 1);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        let result = adc.check_input(Channel::Channel1, 5_000, &mut TotalDelay::default());
+        assert_eq!(
+            result.unwrap_err().operation(),
+            Some(crate::error::Operation::DataRead)
+        );
+        assert_eq!(pin_registers(&mut adc), before);
+    }
+}
@@ -0,0 +1,277 @@
+//! Piecewise-linear scaling of results to engineering units.
+//!
+//! A `PiecewiseLinear` table maps results to values in any unit by interpolating between
+//! breakpoints, such as the points of a sensor curve from a datasheet, using a binary search
+//! and integer arithmetic. Tables can be declared as constants, with
+//! `const _: () = assert!(TABLE.is_ordered());` checking their order at compile time.
+
+use crate::channel::Channel;
+
+/// A result and the value it maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Breakpoint {
+    pub code: u16,
+    pub value: i32,
+}
+
+impl Breakpoint {
+    #[must_use]
+    pub const fn new(code: u16, value: i32) -> Self {
+        Breakpoint { code, value }
+    }
+}
+
+/// A table of breakpoints ordered by strictly rising code.
+///
+/// With the `serde` feature a table serializes as its breakpoints. It borrows them, so it is
+/// not deserialized; deserialize a `Breakpoint` array and build the table over it instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PiecewiseLinear<'a> {
+    points: &'a [Breakpoint],
+}
+
+impl<'a> PiecewiseLinear<'a> {
+    /// Creates a table. The breakpoints must be ordered by strictly rising code, which
+    /// `is_ordered()` checks.
+    #[must_use]
+    pub const fn new(points: &'a [Breakpoint]) -> Self {
+        PiecewiseLinear { points }
+    }
+
+    #[must_use]
+    pub const fn points(&self) -> &'a [Breakpoint] {
+        self.points
+    }
+
+    /// Whether the breakpoints are ordered by strictly rising code.
+    ///
+    /// Being `const`, it can check a table at compile time:
+    ///
+    /// ```
+    /// use tla2528::lookup::{Breakpoint, PiecewiseLinear};
+    ///
+    /// const TABLE: PiecewiseLinear<'static> =
+    ///     PiecewiseLinear::new(&[Breakpoint::new(100, 0), Breakpoint::new(200, 50)]);
+    /// const _: () = assert!(TABLE.is_ordered());
+    /// ```
+    ///
+    /// A table with a repeated or falling code then fails to build:
+    ///
+    /// ```compile_fail
+    /// use tla2528::lookup::{Breakpoint, PiecewiseLinear};
+    ///
+    /// const TABLE: PiecewiseLinear<'static> =
+    ///     PiecewiseLinear::new(&[Breakpoint::new(200, 0), Breakpoint::new(100, 50)]);
+    /// const _: () = assert!(TABLE.is_ordered());
+    /// ```
+    #[must_use]
+    pub const fn is_ordered(&self) -> bool {
+        let mut index = 1;
+        while index < self.points.len() {
+            if self.points[index - 1].code >= self.points[index].code {
+                return false;
+            }
+            index += 1;
+        }
+        true
+    }
+
+    /// The value for a result, or `None` if it is outside the table.
+    #[must_use]
+    pub fn evaluate(&self, code: u16) -> Option<i32> {
+        let index = self.points.partition_point(|point| point.code < code);
+        let upper = self.points.get(index)?;
+        if upper.code == code {
+            return Some(upper.value);
+        }
+        let lower = self.points.get(index.checked_sub(1)?)?;
+        Some(interpolate(
+            (i64::from(lower.code), i64::from(lower.value)),
+            (i64::from(upper.code), i64::from(upper.value)),
+            i64::from(code),
+        ))
+    }
+
+    /// The value for a result, holding the end values outside the table. Returns `None` only
+    /// for an empty table.
+    #[must_use]
+    pub fn evaluate_clamped(&self, code: u16) -> Option<i32> {
+        let first = self.points.first()?;
+        let last = self.points.last()?;
+        if code <= first.code {
+            Some(first.value)
+        } else if code >= last.code {
+            Some(last.value)
+        } else {
+            self.evaluate(code)
+        }
+    }
+}
+
+/// Interpolates linearly between two points, rounding to the nearest integer and saturating at
+/// the limits of an `i32`. `x` should lie between the two points, which must differ in `x`.
+pub(crate) fn interpolate(lower: (i64, i64), upper: (i64, i64), x: i64) -> i32 {
+    let (x0, y0) = lower;
+    let (x1, y1) = upper;
+    let span = x1 - x0;
+    if span == 0 {
+        return i32::try_from(y0).unwrap_or(if y0 < 0 { i32::MIN } else { i32::MAX });
+    }
+    let numerator = (y1 - y0) * (x - x0);
+    let magnitude = (numerator.abs() + span.abs() / 2) / span.abs();
+    let y = if (numerator < 0) == (span < 0) {
+        y0 + magnitude
+    } else {
+        y0 - magnitude
+    };
+    i32::try_from(y).unwrap_or(if y < 0 { i32::MIN } else { i32::MAX })
+}
+
+/// A scaling table for each channel, for converting whole frames. Like `PiecewiseLinear`, it
+/// is serialized but not deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChannelScaling<'a> {
+    tables: [Option<PiecewiseLinear<'a>>; 8],
+}
+
+impl<'a> ChannelScaling<'a> {
+    /// Creates a set with no channel scaled.
+    #[must_use]
+    pub const fn new() -> Self {
+        ChannelScaling { tables: [None; 8] }
+    }
+
+    pub fn set(&mut self, channel: Channel, table: PiecewiseLinear<'a>) {
+        if let Some(slot) = self.tables.get_mut(channel.index()) {
+            *slot = Some(table);
+        }
+    }
+
+    pub fn clear(&mut self, channel: Channel) {
+        if let Some(slot) = self.tables.get_mut(channel.index()) {
+            *slot = None;
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, channel: Channel) -> Option<PiecewiseLinear<'a>> {
+        self.tables.get(channel.index()).copied().flatten()
+    }
+
+    /// The value for a result of one channel, or `None` if the channel has no table or the
+    /// result is outside it.
+    #[must_use]
+    pub fn evaluate(&self, channel: Channel, code: u16) -> Option<i32> {
+        self.get(channel)?.evaluate(code)
+    }
+
+    /// Converts a frame of results indexed by channel.
+    #[must_use]
+    pub fn evaluate_frame(&self, frame: &[u16; 8]) -> [Option<i32>; 8] {
+        let mut values = [None; 8];
+        for ((value, table), &code) in values.iter_mut().zip(&self.tables).zip(frame) {
+            *value = table.and_then(|scaling| scaling.evaluate(code));
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{Breakpoint, ChannelScaling, PiecewiseLinear};
+    use crate::channel::Channel;
+
+    /// A rising curve, in tenths of a unit.
+    const RISING: PiecewiseLinear<'static> = PiecewiseLinear::new(&[
+        Breakpoint::new(400, -100),
+        Breakpoint::new(1000, 200),
+        Breakpoint::new(3000, 1200),
+    ]);
+
+    /// A curve whose value falls as the code rises, like an NTC in a pull-up divider.
+    const FALLING: PiecewiseLinear<'static> = PiecewiseLinear::new(&[
+        Breakpoint::new(500, 1500),
+        Breakpoint::new(2500, 500),
+        Breakpoint::new(3500, -250),
+    ]);
+
+    const _: () = assert!(RISING.is_ordered() && FALLING.is_ordered());
+
+    #[test]
+    fn breakpoints_are_returned_exactly() {
+        for table in [RISING, FALLING] {
+            for point in table.points() {
+                assert_eq!(table.evaluate(point.code), Some(point.value));
+                assert_eq!(table.evaluate_clamped(point.code), Some(point.value));
+            }
+        }
+    }
+
+    #[test]
+    fn values_between_breakpoints_are_interpolated() {
+        assert_eq!(RISING.evaluate(700), Some(50_i32));
+        assert_eq!(RISING.evaluate(2000), Some(700_i32));
+        // Half a tenth per code, with halves rounded away from the lower breakpoint
+        assert_eq!(RISING.evaluate(401), Some(-99_i32));
+        assert_eq!(RISING.evaluate(403), Some(-98_i32));
+        assert_eq!(FALLING.evaluate(1500), Some(1000_i32));
+        assert_eq!(FALLING.evaluate(3000), Some(125_i32));
+        assert_eq!(FALLING.evaluate(3499), Some(-249_i32));
+    }
+
+    #[test]
+    fn results_outside_the_table_are_clamped_or_rejected() {
+        assert_eq!(RISING.evaluate(399), None);
+        assert_eq!(RISING.evaluate(3001), None);
+        assert_eq!(RISING.evaluate_clamped(0), Some(-100_i32));
+        assert_eq!(RISING.evaluate_clamped(u16::MAX), Some(1200_i32));
+        assert_eq!(FALLING.evaluate_clamped(0), Some(1500_i32));
+        assert_eq!(FALLING.evaluate_clamped(4095), Some(-250_i32));
+        let empty = PiecewiseLinear::new(&[]);
+        assert_eq!(empty.evaluate(0), None);
+        assert_eq!(empty.evaluate_clamped(0), None);
+    }
+
+    #[test]
+    fn unordered_tables_are_detected() {
+        let repeated = [Breakpoint::new(100, 0), Breakpoint::new(100, 1)];
+        let falling = [Breakpoint::new(200, 0), Breakpoint::new(100, 1)];
+        assert!(!PiecewiseLinear::new(&repeated).is_ordered());
+        assert!(!PiecewiseLinear::new(&falling).is_ordered());
+        assert!(PiecewiseLinear::new(&[]).is_ordered());
+    }
+
+    #[test]
+    fn frames_are_scaled_per_channel() {
+        let mut scaling = ChannelScaling::new();
+        scaling.set(Channel::Channel0, RISING);
+        scaling.set(Channel::Channel3, FALLING);
+        scaling.set(Channel::Channel5, RISING);
+        scaling.clear(Channel::Channel5);
+        assert_eq!(scaling.get(Channel::Channel3), Some(FALLING));
+        assert_eq!(scaling.evaluate(Channel::Channel1, 1000), None);
+        assert_eq!(
+            scaling.evaluate_frame(&[1000, 1000, 0, 1500, 0, 1000, 0, 0]),
+            [
+                Some(200_i32),
+                None,
+                None,
+                Some(1000_i32),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
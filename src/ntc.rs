@@ -0,0 +1,452 @@
+//! NTC thermistor temperature conversion.
+//!
+//! `Ntc` converts the result of a channel measuring an NTC thermistor in a resistive divider
+//! across AVDD into centi-degrees Celsius. The divider is ratiometric to the ADC reference, so
+//! only the fixed resistor's value is needed. The thermistor's resistance is looked up in a
+//! table of `NtcPoint`s and interpolated linearly; tables for common 10 kohm parts are provided,
+//! precomputed from the Beta and Steinhart-Hart models so no floating point is needed at run
+//! time.
+//!
+//! Tables for other parts are generated on the host with the `ntc_table` example, which prints
+//! one in the same layout from a part's Beta or Steinhart-Hart coefficients:
+//!
+//! ```text
+//! cargo run --example ntc_table -- beta NTC_100K_B4250 100000 4250
+//! cargo run --example ntc_table -- steinhart-hart NTC_10K_SH 1.129148e-3 2.34125e-4 8.76741e-8
+//! ```
+//!
+//! Any table ordered by falling resistance works, so points can also be taken from a
+//! manufacturer's resistance-temperature chart.
+//!
+//! The provided tables have a point every 5 degC from -40 degC to 125 degC. Interpolating
+//! linearly between them is within 0.25 degC of the model they were computed from; the error is
+//! largest midway between points at the cold end, where the curve bends most, and shrinks
+//! towards the hot end. Where that matters, use a table with closer points.
+//!
+//! Results within the fault margin of either end of the code range are reported as an open or
+//! shorted thermistor.
+
+use crate::{chip_definitions::Resolution, lookup::interpolate};
+
+/// A thermistor resistance and the temperature at which it occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NtcPoint {
+    pub ohms: u32,
+    pub centi_celsius: i32,
+}
+
+impl NtcPoint {
+    #[must_use]
+    pub const fn new(ohms: u32, centi_celsius: i32) -> Self {
+        NtcPoint {
+            ohms,
+            centi_celsius,
+        }
+    }
+}
+
+/// 10 kohm at 25 degC, Beta 3950 K.
+pub const NTC_10K_B3950: &[NtcPoint] = &[
+    NtcPoint::new(401_860, -4000),
+    NtcPoint::new(281_577, -3500),
+    NtcPoint::new(200_204, -3000),
+    NtcPoint::new(144_317, -2500),
+    NtcPoint::new(105_385, -2000),
+    NtcPoint::new(77_898, -1500),
+    NtcPoint::new(58_246, -1000),
+    NtcPoint::new(44_026, -500),
+    NtcPoint::new(33_621, 0),
+    NtcPoint::new(25_925, 500),
+    NtcPoint::new(20_175, 1000),
+    NtcPoint::new(15_837, 1500),
+    NtcPoint::new(12_535, 2000),
+    NtcPoint::new(10_000, 2500),
+    NtcPoint::new(8_037, 3000),
+    NtcPoint::new(6_506, 3500),
+    NtcPoint::new(5_301, 4000),
+    NtcPoint::new(4_348, 4500),
+    NtcPoint::new(3_588, 5000),
+    NtcPoint::new(2_978, 5500),
+    NtcPoint::new(2_486, 6000),
+    NtcPoint::new(2_086, 6500),
+    NtcPoint::new(1_760, 7000),
+    NtcPoint::new(1_492, 7500),
+    NtcPoint::new(1_270, 8000),
+    NtcPoint::new(1_087, 8500),
+    NtcPoint::new(934, 9000),
+    NtcPoint::new(805, 9500),
+    NtcPoint::new(698, 10000),
+    NtcPoint::new(606, 10500),
+    NtcPoint::new(529, 11000),
+    NtcPoint::new(463, 11500),
+    NtcPoint::new(407, 12000),
+    NtcPoint::new(359, 12500),
+];
+
+/// 10 kohm at 25 degC, Beta 3435 K.
+pub const NTC_10K_B3435: &[NtcPoint] = &[
+    NtcPoint::new(248_277, -4000),
+    NtcPoint::new(182_221, -3500),
+    NtcPoint::new(135_452, -3000),
+    NtcPoint::new(101_898, -2500),
+    NtcPoint::new(77_523, -2000),
+    NtcPoint::new(59_606, -1500),
+    NtcPoint::new(46_290, -1000),
+    NtcPoint::new(36_290, -500),
+    NtcPoint::new(28_704, 0),
+    NtcPoint::new(22_897, 500),
+    NtcPoint::new(18_410, 1000),
+    NtcPoint::new(14_916, 1500),
+    NtcPoint::new(12_171, 2000),
+    NtcPoint::new(10_000, 2500),
+    NtcPoint::new(8_269, 3000),
+    NtcPoint::new(6_881, 3500),
+    NtcPoint::new(5_759, 4000),
+    NtcPoint::new(4_847, 4500),
+    NtcPoint::new(4_101, 5000),
+    NtcPoint::new(3_488, 5500),
+    NtcPoint::new(2_981, 6000),
+    NtcPoint::new(2_559, 6500),
+    NtcPoint::new(2_207, 7000),
+    NtcPoint::new(1_912, 7500),
+    NtcPoint::new(1_662, 8000),
+    NtcPoint::new(1_451, 8500),
+    NtcPoint::new(1_272, 9000),
+    NtcPoint::new(1_118, 9500),
+    NtcPoint::new(987, 10000),
+    NtcPoint::new(874, 10500),
+    NtcPoint::new(776, 11000),
+    NtcPoint::new(692, 11500),
+    NtcPoint::new(618, 12000),
+    NtcPoint::new(554, 12500),
+];
+
+/// 10 kohm at 25 degC, Steinhart-Hart A = 1.129148e-3, B = 2.34125e-4, C = 8.76741e-8.
+pub const NTC_10K_STEINHART_HART: &[NtcPoint] = &[
+    NtcPoint::new(336_097, -4000),
+    NtcPoint::new(242_426, -3500),
+    NtcPoint::new(176_802, -3000),
+    NtcPoint::new(130_305, -2500),
+    NtcPoint::new(97_005, -2000),
+    NtcPoint::new(72_910, -1500),
+    NtcPoint::new(55_303, -1000),
+    NtcPoint::new(42_316, -500),
+    NtcPoint::new(32_650, 0),
+    NtcPoint::new(25_395, 500),
+    NtcPoint::new(19_903, 1000),
+    NtcPoint::new(15_713, 1500),
+    NtcPoint::new(12_493, 2000),
+    NtcPoint::new(10_000, 2500),
+    NtcPoint::new(8_056, 3000),
+    NtcPoint::new(6_530, 3500),
+    NtcPoint::new(5_325, 4000),
+    NtcPoint::new(4_367, 4500),
+    NtcPoint::new(3_601, 5000),
+    NtcPoint::new(2_985, 5500),
+    NtcPoint::new(2_487, 6000),
+    NtcPoint::new(2_082, 6500),
+    NtcPoint::new(1_752, 7000),
+    NtcPoint::new(1_480, 7500),
+    NtcPoint::new(1_256, 8000),
+    NtcPoint::new(1_070, 8500),
+    NtcPoint::new(916, 9000),
+    NtcPoint::new(787, 9500),
+    NtcPoint::new(678, 10000),
+    NtcPoint::new(587, 10500),
+    NtcPoint::new(510, 11000),
+    NtcPoint::new(444, 11500),
+    NtcPoint::new(388, 12000),
+    NtcPoint::new(341, 12500),
+];
+
+/// How the thermistor and the fixed resistor are connected between AVDD and ground.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Divider {
+    /// The fixed resistor is between AVDD and the input, the thermistor between the input and
+    /// ground. The result rises as the temperature falls.
+    PullUp { ohms: u32 },
+    /// The thermistor is between AVDD and the input, the fixed resistor between the input and
+    /// ground. The result rises with temperature.
+    PullDown { ohms: u32 },
+}
+
+/// Why a result could not be converted to a temperature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NtcFault {
+    /// The thermistor is disconnected.
+    Open,
+    /// The thermistor is shorted.
+    Short,
+    /// The resistance is above the first table entry, colder than the table covers.
+    BelowRange,
+    /// The resistance is below the last table entry, hotter than the table covers.
+    AboveRange,
+}
+
+/// Conversion of results from one thermistor divider.
+///
+/// With the `serde` feature the conversion serializes with its table, for logging a
+/// configuration. The table is borrowed, so the conversion is not deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Ntc<'a> {
+    table: &'a [NtcPoint],
+    divider: Divider,
+    resolution: Resolution,
+    fault_margin: u32,
+}
+
+impl<'a> Ntc<'a> {
+    /// Creates a conversion using `table`, which must be ordered by falling resistance.
+    ///
+    /// The fault margin defaults to 1/512 of full scale.
+    #[must_use]
+    pub const fn new(table: &'a [NtcPoint], divider: Divider, resolution: Resolution) -> Self {
+        Ntc {
+            table,
+            divider,
+            resolution,
+            fault_margin: resolution.full_scale() >> 9_u32,
+        }
+    }
+
+    /// Sets how many codes from either end of the range are treated as an open or short.
+    #[must_use]
+    pub const fn with_fault_margin(self, codes: u32) -> Self {
+        Ntc {
+            fault_margin: codes,
+            ..self
+        }
+    }
+
+    /// Resistance of the thermistor in ohms.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NtcFault::Open` or `NtcFault::Short` for results within the fault margin of the
+    /// ends of the range.
+    pub fn resistance(&self, code: u16) -> Result<u32, NtcFault> {
+        let full_scale = u64::from(self.resolution.full_scale());
+        let result = u64::from(code).min(full_scale);
+        let near_zero = result <= u64::from(self.fault_margin);
+        let near_full_scale = result + u64::from(self.fault_margin) >= full_scale;
+        let ohms = match self.divider {
+            Divider::PullUp { ohms } => {
+                if near_full_scale {
+                    return Err(NtcFault::Open);
+                }
+                if near_zero {
+                    return Err(NtcFault::Short);
+                }
+                u64::from(ohms) * result / (full_scale - result)
+            }
+            Divider::PullDown { ohms } => {
+                if near_zero {
+                    return Err(NtcFault::Open);
+                }
+                if near_full_scale {
+                    return Err(NtcFault::Short);
+                }
+                u64::from(ohms) * (full_scale - result) / result
+            }
+        };
+        Ok(u32::try_from(ohms).unwrap_or(u32::MAX))
+    }
+
+    /// Temperature in centi-degrees Celsius.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NtcFault::Open` or `NtcFault::Short` for results within the fault margin of the
+    /// ends of the range, and `NtcFault::BelowRange` or `NtcFault::AboveRange` for resistances
+    /// outside the table.
+    pub fn centi_celsius(&self, code: u16) -> Result<i32, NtcFault> {
+        let ohms = self.resistance(code)?;
+        let index = self.table.partition_point(|point| point.ohms > ohms);
+        let upper = self.table.get(index).ok_or(NtcFault::AboveRange)?;
+        let Some(lower) = index.checked_sub(1).and_then(|below| self.table.get(below)) else {
+            return if upper.ohms == ohms {
+                Ok(upper.centi_celsius)
+            } else {
+                Err(NtcFault::BelowRange)
+            };
+        };
+        Ok(interpolate(
+            (i64::from(lower.ohms), i64::from(lower.centi_celsius)),
+            (i64::from(upper.ohms), i64::from(upper.centi_celsius)),
+            i64::from(ohms),
+        ))
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::float_arithmetic,
+    clippy::as_conversions,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "The models are checked in floating point; failures report the point and model."
+)]
+mod tests {
+    use super::{
+        Divider, Ntc, NtcFault, NtcPoint, NTC_10K_B3435, NTC_10K_B3950, NTC_10K_STEINHART_HART,
+    };
+    use crate::chip_definitions::Resolution;
+
+    const ZERO_CELSIUS_KELVIN: f64 = 273.15;
+
+    /// A thermistor model, giving the resistance at a temperature in degrees Celsius.
+    type Model<'a> = &'a dyn Fn(f64) -> f64;
+
+    fn beta_ohms(beta: f64, celsius: f64) -> f64 {
+        let kelvin = celsius + ZERO_CELSIUS_KELVIN;
+        10_000.0_f64
+            * (beta * (1.0_f64 / kelvin - 1.0_f64 / (25.0_f64 + ZERO_CELSIUS_KELVIN))).exp()
+    }
+
+    /// Solves `1/T = A + B ln(R) + C ln(R)^3` for `R` with Cardano's formula.
+    fn steinhart_hart_ohms(celsius: f64) -> f64 {
+        let (coeff_a, coeff_b, coeff_c) = (1.129_148e-3_f64, 2.341_25e-4_f64, 8.767_41e-8_f64);
+        let kelvin = celsius + ZERO_CELSIUS_KELVIN;
+        let half_q = (coeff_a - 1.0_f64 / kelvin) / (2.0_f64 * coeff_c);
+        let root = ((coeff_b / (3.0_f64 * coeff_c)).powi(3_i32) + half_q * half_q).sqrt();
+        ((root - half_q).cbrt() - (root + half_q).cbrt()).exp()
+    }
+
+    fn assert_matches_model(table: &[NtcPoint], model: impl Fn(f64) -> f64) {
+        for point in table {
+            let expected = model(f64::from(point.centi_celsius) / 100.0_f64);
+            assert!(
+                (f64::from(point.ohms) - expected).abs() <= 0.5_f64,
+                "{point:?} differs from the model's {expected} ohms"
+            );
+        }
+    }
+
+    #[test]
+    fn tables_follow_their_models() {
+        assert_matches_model(NTC_10K_B3950, |celsius| beta_ohms(3950.0_f64, celsius));
+        assert_matches_model(NTC_10K_B3435, |celsius| beta_ohms(3435.0_f64, celsius));
+        assert_matches_model(NTC_10K_STEINHART_HART, steinhart_hart_ohms);
+    }
+
+    #[test]
+    fn tables_fall_in_resistance() {
+        for table in [NTC_10K_B3950, NTC_10K_B3435, NTC_10K_STEINHART_HART] {
+            assert!(table.windows(2).all(|pair| match *pair {
+                [first, second] =>
+                    first.ohms > second.ohms && first.centi_celsius < second.centi_celsius,
+                _ => false,
+            }));
+        }
+    }
+
+    /// Converts a resistance to the code a 10 kohm pull-up divider gives at 16 bits.
+    fn pull_up_code(ohms: f64) -> u16 {
+        (65536.0_f64 * ohms / (ohms + 10_000.0_f64)).round() as u16
+    }
+
+    #[test]
+    fn interpolation_stays_within_the_documented_bound() {
+        let models: [(&[NtcPoint], Model<'_>); 3] = [
+            (NTC_10K_B3950, &|celsius| beta_ohms(3950.0_f64, celsius)),
+            (NTC_10K_B3435, &|celsius| beta_ohms(3435.0_f64, celsius)),
+            (NTC_10K_STEINHART_HART, &steinhart_hart_ohms),
+        ];
+        for (table, model) in models {
+            let ntc = Ntc::new(table, Divider::PullUp { ohms: 10_000 }, Resolution::Bits16);
+            // Every 0.05 degC strictly inside the table, so quantisation cannot leave it
+            for step in 1_i32..3299_i32 {
+                let celsius = -40.0_f64 + f64::from(step) * 0.05_f64;
+                let read = f64::from(ntc.centi_celsius(pull_up_code(model(celsius))).unwrap());
+                // The bound plus a centi-degree of rounding
+                assert!(
+                    (read / 100.0_f64 - celsius).abs() <= 0.26_f64,
+                    "{read} read at {celsius} degC"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pull_up_resistance_rises_with_the_code() {
+        let ntc = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullUp { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        assert_eq!(ntc.resistance(2048), Ok(10_000));
+        assert_eq!(ntc.resistance(1024), Ok(3333));
+        assert_eq!(ntc.resistance(3072), Ok(30_000));
+        assert_eq!(ntc.centi_celsius(2048), Ok(2500_i32));
+        assert_eq!(ntc.centi_celsius(3157), Ok(0_i32));
+    }
+
+    #[test]
+    fn pull_down_resistance_falls_with_the_code() {
+        let ntc = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullDown { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        assert_eq!(ntc.resistance(2048), Ok(10_000));
+        assert_eq!(ntc.resistance(3072), Ok(3333));
+        assert_eq!(ntc.resistance(1024), Ok(30_000));
+        assert_eq!(ntc.centi_celsius(2048), Ok(2500_i32));
+        assert_eq!(ntc.centi_celsius(939), Ok(0_i32));
+    }
+
+    #[test]
+    fn open_and_shorted_thermistors_are_detected_at_both_ends() {
+        let pull_up = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullUp { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        let pull_down = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullDown { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        // The default margin is 8 codes at 12 bits
+        for code in [4088, 4095, u16::MAX] {
+            assert_eq!(pull_up.resistance(code), Err(NtcFault::Open));
+            assert_eq!(pull_down.centi_celsius(code), Err(NtcFault::Short));
+        }
+        for code in [0, 8] {
+            assert_eq!(pull_up.centi_celsius(code), Err(NtcFault::Short));
+            assert_eq!(pull_down.resistance(code), Err(NtcFault::Open));
+        }
+        assert_eq!(pull_up.resistance(4087), Ok(4_541_111));
+        assert_eq!(pull_up.resistance(9), Ok(22));
+        let no_margin = pull_up.with_fault_margin(0);
+        assert_eq!(no_margin.resistance(4095), Ok(40_950_000));
+        assert_eq!(no_margin.resistance(4096), Err(NtcFault::Open));
+        assert_eq!(no_margin.resistance(0), Err(NtcFault::Short));
+        assert_eq!(no_margin.resistance(1), Ok(2));
+    }
+
+    #[test]
+    fn resistances_outside_the_table_are_reported() {
+        let ntc = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullUp { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        assert_eq!(ntc.centi_celsius(4087), Err(NtcFault::BelowRange));
+        assert_eq!(ntc.centi_celsius(100), Err(NtcFault::AboveRange));
+        assert_eq!(
+            ntc.with_fault_margin(0).centi_celsius(1),
+            Err(NtcFault::AboveRange)
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! Supervision of supply rails measured through dividers.
+//!
+//! `RailMonitor` converts the results of channels wired to supply rails into millivolts and
+//! checks them against each rail's nominal voltage and tolerance. It counts dips, falls between
+//! consecutive frames larger than a rail's dip threshold, and keeps the lowest voltage seen, so
+//! brief brownouts between frames leave a trace.
+//!
+//! The ADC reference is AVDD, so every conversion depends on it. When a channel measures a
+//! known reference voltage, the monitor derives AVDD from it, uses the measured value for the
+//! rails and reports its drift from the assumed value.
+
+use crate::{
+    channel::{Channel, ChannelFlags},
+    chip_definitions::Resolution,
+};
+
+/// Configuration of one supply rail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RailConfig {
+    pub nominal_millivolts: u32,
+    /// Resistor between the rail and the input.
+    pub top_ohms: u32,
+    /// Resistor between the input and ground.
+    pub bottom_ohms: u32,
+    /// Allowed deviation from nominal, in thousandths.
+    pub tolerance_per_mille: u16,
+    /// Fall between consecutive frames, in thousandths of nominal, counted as a dip.
+    pub dip_per_mille: u16,
+}
+
+/// Whether a rail is within tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RailStatus {
+    #[default]
+    Normal,
+    UnderVoltage,
+    OverVoltage,
+}
+
+/// The outcome of evaluating one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RailReport {
+    pub under_voltage: ChannelFlags,
+    pub over_voltage: ChannelFlags,
+    /// Rails that dipped since the previous frame.
+    pub dips: ChannelFlags,
+    /// AVDD used for the conversions, measured if a reference channel is set.
+    pub avdd_microvolts: u32,
+    /// Deviation of the measured AVDD from the assumed value, in thousandths, if a reference
+    /// channel is set.
+    pub avdd_drift_per_mille: Option<i32>,
+}
+
+/// Tracking state of one rail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RailState {
+    pub status: RailStatus,
+    /// Voltage in the latest frame.
+    pub millivolts: Option<u32>,
+    /// Lowest voltage since tracking was reset.
+    pub min_millivolts: Option<u32>,
+    /// Highest voltage since tracking was reset.
+    pub max_millivolts: Option<u32>,
+    /// Dips since tracking was reset.
+    pub dips: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Reference {
+    channel: Channel,
+    microvolts: u32,
+}
+
+/// Per-channel supply rail supervision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RailMonitor {
+    avdd_microvolts: u32,
+    resolution: Resolution,
+    rails: [Option<RailConfig>; 8],
+    states: [RailState; 8],
+    reference: Option<Reference>,
+}
+
+impl RailMonitor {
+    /// Creates a monitor with no rails, converting with the assumed AVDD.
+    #[must_use]
+    pub const fn new(avdd_microvolts: u32, resolution: Resolution) -> Self {
+        RailMonitor {
+            avdd_microvolts,
+            resolution,
+            rails: [None; 8],
+            states: [RailState {
+                status: RailStatus::Normal,
+                millivolts: None,
+                min_millivolts: None,
+                max_millivolts: None,
+                dips: 0,
+            }; 8],
+            reference: None,
+        }
+    }
+
+    /// Monitors a rail on `channel`, discarding its tracking state.
+    pub fn set_rail(&mut self, channel: Channel, config: RailConfig) {
+        if let Some(rail) = self.rails.get_mut(channel.index()) {
+            *rail = Some(config);
+        }
+        self.reset_channel(channel);
+    }
+
+    pub fn clear_rail(&mut self, channel: Channel) {
+        if let Some(rail) = self.rails.get_mut(channel.index()) {
+            *rail = None;
+        }
+        self.reset_channel(channel);
+    }
+
+    /// Derives AVDD from `channel`, which measures a reference of `microvolts` directly.
+    pub fn set_reference(&mut self, channel: Channel, microvolts: u32) {
+        self.reference = Some(Reference {
+            channel,
+            microvolts,
+        });
+    }
+
+    pub fn clear_reference(&mut self) {
+        self.reference = None;
+    }
+
+    #[must_use]
+    pub fn state(&self, channel: Channel) -> RailState {
+        self.states
+            .get(channel.index())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Clears the minimum, maximum and dip count of every rail.
+    pub fn reset_tracking(&mut self) {
+        self.states = [RailState::default(); 8];
+    }
+
+    fn reset_channel(&mut self, channel: Channel) {
+        if let Some(state) = self.states.get_mut(channel.index()) {
+            *state = RailState::default();
+        }
+    }
+
+    /// AVDD measured from the reference channel, if one is set and reads above zero.
+    fn measured_avdd(&self, frame: &[u16; 8]) -> Option<u32> {
+        let reference = self.reference?;
+        let code = u64::from(*frame.get(reference.channel.index())?);
+        if code == 0 {
+            return None;
+        }
+        let avdd = u64::from(reference.microvolts) * u64::from(self.resolution.full_scale()) / code;
+        u32::try_from(avdd).ok()
+    }
+
+    /// Converts and checks a frame of results indexed by channel.
+    pub fn evaluate(&mut self, frame: &[u16; 8]) -> RailReport {
+        let measured_avdd = self.measured_avdd(frame);
+        let avdd_microvolts = measured_avdd.unwrap_or(self.avdd_microvolts);
+        let mut report = RailReport {
+            avdd_microvolts,
+            avdd_drift_per_mille: measured_avdd.and_then(|avdd| {
+                let assumed = i64::from(self.avdd_microvolts.max(1));
+                i32::try_from((i64::from(avdd) - assumed) * 1000 / assumed).ok()
+            }),
+            ..RailReport::default()
+        };
+        let full_scale = u64::from(self.resolution.full_scale());
+        for (((channel, rail), state), &code) in Channel::ALL
+            .into_iter()
+            .zip(&self.rails)
+            .zip(&mut self.states)
+            .zip(frame)
+        {
+            let Some(config) = *rail else {
+                continue;
+            };
+            let input_microvolts = u64::from(code) * u64::from(avdd_microvolts) / full_scale;
+            let total_ohms = u64::from(config.top_ohms) + u64::from(config.bottom_ohms);
+            let millivolts = u32::try_from(
+                input_microvolts * total_ohms / u64::from(config.bottom_ohms.max(1)) / 1000,
+            )
+            .unwrap_or(u32::MAX);
+
+            let nominal = u64::from(config.nominal_millivolts);
+            let tolerance = nominal * u64::from(config.tolerance_per_mille) / 1000;
+            state.status = if u64::from(millivolts) + tolerance < nominal {
+                report.under_voltage |= ChannelFlags::from(channel);
+                RailStatus::UnderVoltage
+            } else if u64::from(millivolts) > nominal + tolerance {
+                report.over_voltage |= ChannelFlags::from(channel);
+                RailStatus::OverVoltage
+            } else {
+                RailStatus::Normal
+            };
+
+            let dip_threshold = nominal * u64::from(config.dip_per_mille) / 1000;
+            if state
+                .millivolts
+                .is_some_and(|previous| u64::from(previous) > u64::from(millivolts) + dip_threshold)
+            {
+                state.dips = state.dips.saturating_add(1);
+                report.dips |= ChannelFlags::from(channel);
+            }
+            state.millivolts = Some(millivolts);
+            state.min_millivolts = Some(
+                state
+                    .min_millivolts
+                    .map_or(millivolts, |min| min.min(millivolts)),
+            );
+            state.max_millivolts = Some(
+                state
+                    .max_millivolts
+                    .map_or(millivolts, |max| max.max(millivolts)),
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{RailConfig, RailMonitor, RailReport, RailState, RailStatus};
+    use crate::{
+        channel::{Channel, ChannelFlags},
+        chip_definitions::Resolution,
+    };
+
+    /// 1 mV per code at 12 bits.
+    const AVDD_MICROVOLTS: u32 = 4_096_000;
+
+    /// 12 V through a 4:1 divider, so each code is 4 mV, within 5 %, dipping at 240 mV.
+    const RAIL_12V: RailConfig = RailConfig {
+        nominal_millivolts: 12_000,
+        top_ohms: 30_000,
+        bottom_ohms: 10_000,
+        tolerance_per_mille: 50,
+        dip_per_mille: 20,
+    };
+
+    /// 3.3 V measured directly, within 3 %.
+    const RAIL_3V3: RailConfig = RailConfig {
+        nominal_millivolts: 3300,
+        top_ohms: 0,
+        bottom_ohms: 10_000,
+        tolerance_per_mille: 30,
+        dip_per_mille: 100,
+    };
+
+    fn monitor() -> RailMonitor {
+        let mut monitor = RailMonitor::new(AVDD_MICROVOLTS, Resolution::Bits12);
+        monitor.set_rail(Channel::Channel0, RAIL_12V);
+        monitor.set_rail(Channel::Channel1, RAIL_3V3);
+        monitor
+    }
+
+    /// A frame with the 12 V and 3.3 V rails at the given codes.
+    fn frame(rail_12v: u16, rail_3v3: u16) -> [u16; 8] {
+        [rail_12v, rail_3v3, 0, 0, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn rails_are_checked_against_their_tolerance() {
+        let mut monitor = monitor();
+        // 11.4 V and 12.6 V are the edges of the 5 % window
+        let edges = monitor.evaluate(&frame(2850, 3300));
+        assert_eq!(
+            edges,
+            RailReport {
+                avdd_microvolts: AVDD_MICROVOLTS,
+                ..RailReport::default()
+            }
+        );
+        assert_eq!(monitor.state(Channel::Channel0).millivolts, Some(11_400));
+        assert_eq!(
+            monitor.evaluate(&frame(3150, 3300)).over_voltage,
+            ChannelFlags::empty()
+        );
+
+        let outside = monitor.evaluate(&frame(2849, 3400));
+        assert_eq!(outside.under_voltage, ChannelFlags::CHANNEL0);
+        assert_eq!(outside.over_voltage, ChannelFlags::CHANNEL1);
+        assert_eq!(
+            monitor.state(Channel::Channel0).status,
+            RailStatus::UnderVoltage
+        );
+        assert_eq!(
+            monitor.state(Channel::Channel1).status,
+            RailStatus::OverVoltage
+        );
+
+        let swapped = monitor.evaluate(&frame(3151, 3200));
+        assert_eq!(swapped.under_voltage, ChannelFlags::CHANNEL1);
+        assert_eq!(swapped.over_voltage, ChannelFlags::CHANNEL0);
+        let recovered = monitor.evaluate(&frame(3000, 3300));
+        assert_eq!(
+            recovered.under_voltage | recovered.over_voltage,
+            ChannelFlags::empty()
+        );
+        assert_eq!(monitor.state(Channel::Channel0).status, RailStatus::Normal);
+        assert_eq!(monitor.state(Channel::Channel1).status, RailStatus::Normal);
+    }
+
+    #[test]
+    fn falls_beyond_the_dip_threshold_are_counted() {
+        let mut monitor = monitor();
+        monitor.evaluate(&frame(3000, 3300));
+        // 240 mV is not a dip, 244 mV is
+        assert_eq!(
+            monitor.evaluate(&frame(2940, 3300)).dips,
+            ChannelFlags::empty()
+        );
+        monitor.evaluate(&frame(3000, 3300));
+        assert_eq!(
+            monitor.evaluate(&frame(2939, 3300)).dips,
+            ChannelFlags::CHANNEL0
+        );
+        // A slow fall is not a dip, however far it goes
+        for code in [2900, 2850, 2800, 2750] {
+            assert_eq!(
+                monitor.evaluate(&frame(code, 3300)).dips,
+                ChannelFlags::empty()
+            );
+        }
+        assert_eq!(
+            monitor.evaluate(&frame(2000, 2900)).dips,
+            ChannelFlags::CHANNEL0 | ChannelFlags::CHANNEL1
+        );
+        assert_eq!(monitor.state(Channel::Channel0).dips, 2);
+        assert_eq!(monitor.state(Channel::Channel1).dips, 1);
+    }
+
+    #[test]
+    fn minimum_and_maximum_are_tracked_until_reset() {
+        let mut monitor = monitor();
+        for code in [3000, 2500, 3100, 2900] {
+            monitor.evaluate(&frame(code, 3300));
+        }
+        assert_eq!(
+            monitor.state(Channel::Channel0),
+            RailState {
+                status: RailStatus::Normal,
+                millivolts: Some(11_600),
+                min_millivolts: Some(10_000),
+                max_millivolts: Some(12_400),
+                dips: 2,
+            }
+        );
+        monitor.reset_tracking();
+        assert_eq!(monitor.state(Channel::Channel0), RailState::default());
+        monitor.evaluate(&frame(2950, 3300));
+        assert_eq!(
+            monitor.state(Channel::Channel0).min_millivolts,
+            Some(11_800)
+        );
+        assert_eq!(
+            monitor.state(Channel::Channel0).max_millivolts,
+            Some(11_800)
+        );
+    }
+
+    #[test]
+    fn unmonitored_channels_are_ignored() {
+        let mut monitor = monitor();
+        monitor.clear_rail(Channel::Channel1);
+        let report = monitor.evaluate(&[3000, 0, 4095, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            report.under_voltage | report.over_voltage,
+            ChannelFlags::empty()
+        );
+        assert_eq!(monitor.state(Channel::Channel1), RailState::default());
+        assert_eq!(monitor.state(Channel::Channel2), RailState::default());
+    }
+
+    #[test]
+    fn reference_channel_corrects_for_avdd_drift() {
+        let mut monitor = monitor();
+        monitor.set_reference(Channel::Channel7, 2_500_000);
+        // AVDD has sagged to 4.0 V, so every code reads 2.4 % high
+        let mut codes = frame(3072, 3379);
+        codes[Channel::Channel7.index()] = 2560;
+        let report = monitor.evaluate(&codes);
+        assert_eq!(report.avdd_microvolts, 4_000_000);
+        assert_eq!(report.avdd_drift_per_mille, Some(-23_i32));
+        assert_eq!(monitor.state(Channel::Channel0).millivolts, Some(12_000));
+        assert_eq!(monitor.state(Channel::Channel1).millivolts, Some(3299));
+
+        monitor.clear_reference();
+        let uncorrected = monitor.evaluate(&codes);
+        assert_eq!(uncorrected.avdd_microvolts, AVDD_MICROVOLTS);
+        assert_eq!(uncorrected.avdd_drift_per_mille, None);
+        assert_eq!(monitor.state(Channel::Channel0).millivolts, Some(12_288));
+    }
+
+    #[test]
+    fn a_reference_reading_zero_falls_back_to_the_assumed_avdd() {
+        let mut monitor = monitor();
+        monitor.set_reference(Channel::Channel7, 2_500_000);
+        let report = monitor.evaluate(&frame(3000, 3300));
+        assert_eq!(report.avdd_microvolts, AVDD_MICROVOLTS);
+        assert_eq!(report.avdd_drift_per_mille, None);
+        assert_eq!(monitor.state(Channel::Channel0).millivolts, Some(12_000));
+    }
+}
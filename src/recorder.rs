@@ -0,0 +1,574 @@
+//! Capture and replay of I2C bus traffic.
+//!
+//! `Recorder` wraps any `embedded_hal::i2c::I2c` bus and appends every operation to a
+//! caller-supplied byte buffer, so traffic from a unit in the field can be captured without an
+//! allocator. `Replay` is an `I2c` bus that serves a captured log back, checking that the same
+//! writes are made and returning the recorded read data and errors, for deterministic
+//! regression tests.
+//!
+//! Each log entry is `[tag, address, length_lo, length_hi, data...]`. The tag holds the
+//! operation kind, with `CONTINUED` set on operations joined to the previous one by a repeated
+//! start. A failed transaction is logged as a single `[TAG_ERROR, address, error]` entry in
+//! place of its operations.
+
+use embedded_hal::i2c::{
+    ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress,
+};
+
+const TAG_WRITE: u8 = 0x01;
+const TAG_READ: u8 = 0x02;
+const TAG_ERROR: u8 = 0x03;
+const TAG_KIND_MASK: u8 = 0x0F;
+const CONTINUED: u8 = 0x80;
+const OPERATION_HEADER_LEN: usize = 4;
+const ERROR_ENTRY_LEN: usize = 3;
+
+/// One entry of a recorded log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Record<'a> {
+    Write {
+        address: u8,
+        data: &'a [u8],
+        continued: bool,
+    },
+    Read {
+        address: u8,
+        data: &'a [u8],
+        continued: bool,
+    },
+    Error {
+        address: u8,
+        kind: ErrorKind,
+    },
+}
+
+/// Iterates over the entries of a recorded log. Iteration stops at the first malformed entry.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Records<'a> {
+    log: &'a [u8],
+}
+
+impl<'a> Records<'a> {
+    #[must_use]
+    pub fn new(log: &'a [u8]) -> Self {
+        Records { log }
+    }
+
+    fn peek(&self) -> Option<(Record<'a>, usize)> {
+        match *self.log {
+            [tag, address, error, ..] if tag == TAG_ERROR => Some((
+                Record::Error {
+                    address,
+                    kind: decode_error_kind(error),
+                },
+                ERROR_ENTRY_LEN,
+            )),
+            [tag, address, length_lo, length_hi, ref rest @ ..] => {
+                let length = usize::from(u16::from_le_bytes([length_lo, length_hi]));
+                let data = rest.get(..length)?;
+                let continued = tag & CONTINUED != 0;
+                let record = match tag & TAG_KIND_MASK {
+                    TAG_WRITE => Record::Write {
+                        address,
+                        data,
+                        continued,
+                    },
+                    TAG_READ => Record::Read {
+                        address,
+                        data,
+                        continued,
+                    },
+                    _ => return None,
+                };
+                Some((record, OPERATION_HEADER_LEN + length))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided Iterator adapters need no specialisation for log records."
+)]
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (record, length) = self.peek()?;
+        self.log = self.log.get(length..).unwrap_or(&[]);
+        Some(record)
+    }
+}
+
+#[allow(
+    clippy::wildcard_enum_match_arm,
+    reason = "ErrorKind is non_exhaustive, unknown kinds are logged as Other."
+)]
+fn encode_error_kind(kind: ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::Bus => 1,
+        ErrorKind::ArbitrationLoss => 2,
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => 3,
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => 4,
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown) => 5,
+        ErrorKind::Overrun => 6,
+        _ => 0,
+    }
+}
+
+fn decode_error_kind(code: u8) -> ErrorKind {
+    match code {
+        1 => ErrorKind::Bus,
+        2 => ErrorKind::ArbitrationLoss,
+        3 => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+        4 => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+        5 => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+        6 => ErrorKind::Overrun,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// An I2C bus wrapper that records all traffic into a byte buffer.
+///
+/// Recording stops, without affecting the bus, once the buffer cannot hold the next entry;
+/// `is_truncated()` reports when that has happened.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Recorder<'b, I2C> {
+    i2c: I2C,
+    log: &'b mut [u8],
+    len: usize,
+    truncated: bool,
+}
+
+impl<'b, I2C> Recorder<'b, I2C> {
+    pub fn new(i2c: I2C, log: &'b mut [u8]) -> Self {
+        Recorder {
+            i2c,
+            log,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// The log recorded so far.
+    #[must_use]
+    pub fn log(&self) -> &[u8] {
+        self.log.get(..self.len).unwrap_or(&[])
+    }
+
+    /// Whether entries were dropped because the buffer was full.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Discards the recorded log.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+
+    /// Releases the wrapped bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn append(&mut self, header: &[u8], data: &[u8]) {
+        if self.truncated {
+            return;
+        }
+        let end = self.len + header.len() + data.len();
+        let Some(entry) = self.log.get_mut(self.len..end) else {
+            self.truncated = true;
+            return;
+        };
+        let (entry_header, entry_data) = entry.split_at_mut(header.len());
+        entry_header.copy_from_slice(header);
+        entry_data.copy_from_slice(data);
+        self.len = end;
+    }
+
+    fn append_operation(&mut self, tag: u8, address: u8, data: &[u8]) {
+        let Ok(length) = u16::try_from(data.len()) else {
+            self.truncated = true;
+            return;
+        };
+        let [length_lo, length_hi] = length.to_le_bytes();
+        self.append(&[tag, address, length_lo, length_hi], data);
+    }
+}
+
+impl<I2C> ErrorType for Recorder<'_, I2C>
+where
+    I2C: ErrorType,
+{
+    type Error = I2C::Error;
+}
+
+impl<I2C> I2c<SevenBitAddress> for Recorder<'_, I2C>
+where
+    I2C: I2c<SevenBitAddress>,
+{
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        use embedded_hal::i2c::Error as _;
+
+        if let Err(err) = self.i2c.transaction(address, operations) {
+            self.append(&[TAG_ERROR, address, encode_error_kind(err.kind())], &[]);
+            return Err(err);
+        }
+        for (index, operation) in operations.iter().enumerate() {
+            let continued = if index == 0 { 0 } else { CONTINUED };
+            match *operation {
+                Operation::Write(data) => {
+                    self.append_operation(TAG_WRITE | continued, address, data);
+                }
+                Operation::Read(ref data) => {
+                    self.append_operation(TAG_READ | continued, address, data);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Read(read)])
+    }
+
+    fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Write(write)])
+    }
+
+    fn write_read(
+        &mut self,
+        address: SevenBitAddress,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.transaction(
+            address,
+            &mut [Operation::Write(write), Operation::Read(read)],
+        )
+    }
+}
+
+/// An I2C bus that replays a log captured by `Recorder`.
+///
+/// Each transaction must match the next recorded one: same address, same operation kinds and
+/// lengths, and identical written bytes. Reads return the recorded data and recorded errors are
+/// returned as they occurred. A transaction that does not match fails with `ErrorKind::Other`
+/// and marks the replay as diverged.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Replay<'a> {
+    records: Records<'a>,
+    diverged: bool,
+}
+
+impl<'a> Replay<'a> {
+    #[must_use]
+    pub fn new(log: &'a [u8]) -> Self {
+        Replay {
+            records: Records::new(log),
+            diverged: false,
+        }
+    }
+
+    /// Whether a transaction failed to match the log.
+    #[must_use]
+    pub fn has_diverged(&self) -> bool {
+        self.diverged
+    }
+
+    /// Whether every recorded entry has been replayed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.records.peek().is_none()
+    }
+
+    fn replay_operation(
+        &mut self,
+        address: SevenBitAddress,
+        operation: &mut Operation<'_>,
+        continued: bool,
+    ) -> bool {
+        match (self.records.next(), operation) {
+            (
+                Some(Record::Write {
+                    address: recorded_address,
+                    data,
+                    continued: recorded_continued,
+                }),
+                &mut Operation::Write(written),
+            ) => recorded_address == address && recorded_continued == continued && data == written,
+            (
+                Some(Record::Read {
+                    address: recorded_address,
+                    data,
+                    continued: recorded_continued,
+                }),
+                &mut Operation::Read(ref mut buffer),
+            ) if recorded_address == address
+                && recorded_continued == continued
+                && data.len() == buffer.len() =>
+            {
+                buffer.copy_from_slice(data);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ErrorType for Replay<'_> {
+    type Error = ErrorKind;
+}
+
+impl I2c<SevenBitAddress> for Replay<'_> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if self.diverged {
+            return Err(ErrorKind::Other);
+        }
+        if let Some((
+            Record::Error {
+                address: recorded_address,
+                kind,
+            },
+            _,
+        )) = self.records.peek()
+        {
+            self.records.next();
+            if recorded_address == address {
+                return Err(kind);
+            }
+            self.diverged = true;
+            return Err(ErrorKind::Other);
+        }
+        for (index, operation) in operations.iter_mut().enumerate() {
+            if !self.replay_operation(address, operation, index != 0) {
+                self.diverged = true;
+                return Err(ErrorKind::Other);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Read(read)])
+    }
+
+    fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Write(write)])
+    }
+
+    fn write_read(
+        &mut self,
+        address: SevenBitAddress,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.transaction(
+            address,
+            &mut [Operation::Write(write), Operation::Read(read)],
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use embedded_hal::i2c::{ErrorKind, I2c, NoAcknowledgeSource};
+
+    use super::{Record, Recorder, Records, Replay};
+    use crate::{
+        channel::Channel,
+        chip_definitions::{Oversampling, RegisterAddress, SystemStatusFlags},
+        error::{Error, Operation},
+        sim::SimulatedTla2528,
+        transport::I2cTransport,
+        Tla2528,
+    };
+
+    const ADDRESS: u8 = 0x10;
+
+    /// Results of a short session: an auto-sequence frame, an oversampled manual conversion
+    /// and the status register.
+    type Session = ([u16; 8], u16, SystemStatusFlags);
+
+    fn run_session<I2C>(adc: &mut Tla2528<I2cTransport<I2C>>) -> Session
+    where
+        I2C: I2c,
+    {
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        let frame = adc.acquire_data().unwrap();
+        adc.set_oversampling_ratio(Oversampling::Ratio4).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        let sample = adc.acquire_channel_data(Channel::Channel3).unwrap();
+        let status = adc.get_system_status().unwrap();
+        (frame, sample, status)
+    }
+
+    fn simulated() -> SimulatedTla2528<'static> {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        for (channel, code) in Channel::ALL.into_iter().zip((0_u16..).step_by(0x111)) {
+            sim.set_input(channel, code);
+        }
+        sim
+    }
+
+    #[test]
+    fn replay_reproduces_a_recorded_session() {
+        let mut sim = simulated();
+        let mut buffer = [0_u8; 1024];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        let original = run_session(&mut Tla2528::new(&mut recorder, ADDRESS));
+        assert!(!recorder.is_truncated());
+        let log = recorder.log().to_vec();
+        assert_eq!(original.1, 0x3330);
+
+        let mut replay = Replay::new(&log);
+        let repeated = run_session(&mut Tla2528::new(&mut replay, ADDRESS));
+        assert_eq!(repeated, original);
+        assert!(replay.is_finished());
+        assert!(!replay.has_diverged());
+    }
+
+    #[test]
+    fn entries_are_tagged_with_address_and_length() {
+        let mut sim = simulated();
+        let mut buffer = [0_u8; 64];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        Tla2528::new(&mut recorder, ADDRESS)
+            .read_register(RegisterAddress::OsrConfig)
+            .unwrap();
+        let osr = RegisterAddress::OsrConfig.value();
+        // A write of the read command, then the register value read after a repeated start
+        assert_eq!(
+            recorder.log(),
+            [0x01, ADDRESS, 2, 0, 0x10, osr, 0x82, ADDRESS, 1, 0, 0x00]
+        );
+        assert_eq!(
+            Records::new(recorder.log()).collect::<Vec<_>>(),
+            [
+                Record::Write {
+                    address: ADDRESS,
+                    data: &[0x10, osr],
+                    continued: false,
+                },
+                Record::Read {
+                    address: ADDRESS,
+                    data: &[0x00],
+                    continued: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diverging_writes_and_addresses_are_rejected() {
+        let mut sim = simulated();
+        let mut buffer = [0_u8; 64];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        recorder.write(ADDRESS, &[0x08, 0x03, 0x04]).unwrap();
+        let log = recorder.log().to_vec();
+
+        let mut different_data = Replay::new(&log);
+        assert_eq!(
+            different_data.write(ADDRESS, &[0x08, 0x03, 0x05]),
+            Err(ErrorKind::Other)
+        );
+        assert!(different_data.has_diverged());
+        // Once diverged, the replay rejects every later transaction
+        assert_eq!(
+            different_data.write(ADDRESS, &[0x08, 0x03, 0x04]),
+            Err(ErrorKind::Other)
+        );
+
+        let mut different_address = Replay::new(&log);
+        assert_eq!(
+            different_address.write(ADDRESS + 1, &[0x08, 0x03, 0x04]),
+            Err(ErrorKind::Other)
+        );
+        assert!(different_address.has_diverged());
+
+        let mut matching = Replay::new(&log);
+        matching.write(ADDRESS, &[0x08, 0x03, 0x04]).unwrap();
+        assert!(matching.is_finished());
+    }
+
+    #[test]
+    fn bus_errors_are_recorded_and_replayed() {
+        let nack = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+        let mut sim = simulated();
+        sim.inject_bus_error(nack, 0, 1);
+        let mut buffer = [0_u8; 64];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        assert!(Tla2528::new(&mut recorder, ADDRESS)
+            .get_system_status()
+            .is_err());
+        assert_eq!(recorder.log(), [0x03, ADDRESS, 3]);
+        let log = recorder.log().to_vec();
+        assert_eq!(
+            Records::new(&log).collect::<Vec<_>>(),
+            [Record::Error {
+                address: ADDRESS,
+                kind: nack,
+            }]
+        );
+
+        let mut replay = Replay::new(&log);
+        let err = Tla2528::new(&mut replay, ADDRESS)
+            .get_system_status()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Bus {
+                operation: Operation::RegisterRead(RegisterAddress::SystemStatus),
+                source,
+            } if source == nack
+        ));
+        assert!(replay.is_finished());
+        assert!(!replay.has_diverged());
+    }
+
+    #[test]
+    fn truncated_logs_end_at_the_last_whole_entry() {
+        let mut sim = simulated();
+        // Room for the first write entry only
+        let mut buffer = [0_u8; 8];
+        let mut recorder = Recorder::new(&mut sim, &mut buffer);
+        recorder.write(ADDRESS, &[0x08, 0x03, 0x04]).unwrap();
+        recorder.write(ADDRESS, &[0x08, 0x03, 0x00]).unwrap();
+        assert!(recorder.is_truncated());
+        assert_eq!(recorder.log().len(), 7);
+
+        // A log cut inside an entry yields the entries before it
+        let log = [
+            0x01, ADDRESS, 3, 0, 0x08, 0x03, 0x04, 0x01, ADDRESS, 3, 0, 0x08,
+        ];
+        assert_eq!(Records::new(&log).count(), 1);
+        let mut replay = Replay::new(&log);
+        replay.write(ADDRESS, &[0x08, 0x03, 0x04]).unwrap();
+        assert!(replay.is_finished());
+        assert_eq!(
+            replay.write(ADDRESS, &[0x08, 0x03, 0x00]),
+            Err(ErrorKind::Other)
+        );
+        assert!(replay.has_diverged());
+    }
+}
@@ -0,0 +1,473 @@
+//! Conversion of results to engineering values through sensor models.
+//!
+//! A `SensorModel` turns a result into a `Reading`: a value in the model's unit and whether the
+//! result was inside the sensor's valid range. Models are provided for the usual front ends,
+//! and `lookup::PiecewiseLinear` and `ntc::Ntc` are models too. `ChannelModels` attaches a model
+//! to each channel for `Tla2528::acquire_readings()`.
+//!
+//! Models that need absolute voltages take AVDD, which is the ADC reference, in microvolts.
+
+use crate::{
+    channel::Channel,
+    chip_definitions::Resolution,
+    lookup::{interpolate, PiecewiseLinear},
+    ntc::{Ntc, NtcFault},
+};
+
+/// Whether a result was inside the sensor's valid range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Validity {
+    Valid,
+    /// Below the valid range, as for a broken current loop.
+    UnderRange,
+    /// Above the valid range, or the ADC input is saturated.
+    OverRange,
+    /// The sensor is disconnected or shorted, or is signalling a fault of its own.
+    Fault,
+}
+
+/// A converted result. The value is a best effort when the reading is not valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reading {
+    pub value: i32,
+    pub validity: Validity,
+}
+
+impl Reading {
+    #[must_use]
+    pub const fn valid(value: i32) -> Self {
+        Reading {
+            value,
+            validity: Validity::Valid,
+        }
+    }
+
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.validity == Validity::Valid
+    }
+}
+
+/// Converts a result of one channel to an engineering value.
+pub trait SensorModel {
+    fn convert(&self, code: u16) -> Reading;
+}
+
+/// Voltage at the ADC input in microvolts.
+fn input_microvolts(code: u16, avdd_microvolts: u32, resolution: Resolution) -> i64 {
+    i64::from(code) * i64::from(avdd_microvolts) / i64::from(resolution.full_scale())
+}
+
+/// Whether a result is at the top of the range, so the input may be above the reference.
+fn is_saturated(code: u16, resolution: Resolution) -> bool {
+    u32::from(code) + 1 >= resolution.full_scale()
+}
+
+/// Maps `x` linearly from the span `from` onto the span `to`.
+fn scale(x: i64, from: (i64, i64), to: (i32, i32)) -> i32 {
+    interpolate((from.0, i64::from(to.0)), (from.1, i64::from(to.1)), x)
+}
+
+/// A resistive divider scaling a voltage down to the ADC input. Readings are the voltage at
+/// the top of the divider in millivolts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoltageDivider {
+    pub top_ohms: u32,
+    pub bottom_ohms: u32,
+    pub avdd_microvolts: u32,
+    pub resolution: Resolution,
+}
+
+impl SensorModel for VoltageDivider {
+    fn convert(&self, code: u16) -> Reading {
+        let microvolts = input_microvolts(code, self.avdd_microvolts, self.resolution);
+        let total_ohms = i64::from(self.top_ohms) + i64::from(self.bottom_ohms);
+        let millivolts = microvolts * total_ohms / i64::from(self.bottom_ohms.max(1)) / 1000;
+        Reading {
+            value: i32::try_from(millivolts).unwrap_or(i32::MAX),
+            validity: if is_saturated(code, self.resolution) {
+                Validity::OverRange
+            } else {
+                Validity::Valid
+            },
+        }
+    }
+}
+
+/// A 4-20 mA current loop across a shunt resistor. Readings are scaled linearly from 4 mA at
+/// `low` to 20 mA at `high`.
+///
+/// Following NAMUR NE 43, currents below 3.8 mA are under range and above 20.5 mA over range,
+/// and currents of 3.6 mA or less and 21 mA or more signal a transmitter fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CurrentLoop {
+    pub shunt_milliohms: u32,
+    pub avdd_microvolts: u32,
+    pub resolution: Resolution,
+    pub low: i32,
+    pub high: i32,
+}
+
+impl CurrentLoop {
+    const MIN_MICROAMPS: i64 = 4_000;
+    const MAX_MICROAMPS: i64 = 20_000;
+    const UNDER_RANGE_MICROAMPS: i64 = 3_800;
+    const OVER_RANGE_MICROAMPS: i64 = 20_500;
+    const FAULT_LOW_MICROAMPS: i64 = 3_600;
+    const FAULT_HIGH_MICROAMPS: i64 = 21_000;
+
+    /// Loop current in microamps.
+    #[must_use]
+    pub fn microamps(&self, code: u16) -> i64 {
+        input_microvolts(code, self.avdd_microvolts, self.resolution) * 1000
+            / i64::from(self.shunt_milliohms.max(1))
+    }
+}
+
+impl SensorModel for CurrentLoop {
+    fn convert(&self, code: u16) -> Reading {
+        let microamps = self.microamps(code);
+        let validity = if microamps <= CurrentLoop::FAULT_LOW_MICROAMPS
+            || microamps >= CurrentLoop::FAULT_HIGH_MICROAMPS
+        {
+            Validity::Fault
+        } else if microamps < CurrentLoop::UNDER_RANGE_MICROAMPS {
+            Validity::UnderRange
+        } else if microamps > CurrentLoop::OVER_RANGE_MICROAMPS
+            || is_saturated(code, self.resolution)
+        {
+            Validity::OverRange
+        } else {
+            Validity::Valid
+        };
+        Reading {
+            value: scale(
+                microamps,
+                (CurrentLoop::MIN_MICROAMPS, CurrentLoop::MAX_MICROAMPS),
+                (self.low, self.high),
+            ),
+            validity,
+        }
+    }
+}
+
+/// A ratiometric transducer, such as a 0.5-4.5 V pressure sensor, supplied from AVDD so its
+/// output runs from 10 % to 90 % of full scale. Readings are scaled linearly from `low` at
+/// 10 % to `high` at 90 %.
+///
+/// Outputs below 8 % or above 92 % of full scale, as from a broken wire, are out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ratiometric {
+    pub resolution: Resolution,
+    pub low: i32,
+    pub high: i32,
+}
+
+impl SensorModel for Ratiometric {
+    fn convert(&self, code: u16) -> Reading {
+        let full_scale = i64::from(self.resolution.full_scale());
+        let per_mille = i64::from(code) * 1000 / full_scale;
+        let validity = if per_mille < 80 {
+            Validity::UnderRange
+        } else if per_mille > 920 {
+            Validity::OverRange
+        } else {
+            Validity::Valid
+        };
+        Reading {
+            value: scale(
+                i64::from(code),
+                (full_scale / 10, full_scale * 9 / 10),
+                (self.low, self.high),
+            ),
+            validity,
+        }
+    }
+}
+
+/// A potentiometer with its track across AVDD and the wiper on the input. Readings are scaled
+/// linearly from `low` at one end of the track to `high` at the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Potentiometer {
+    pub resolution: Resolution,
+    pub low: i32,
+    pub high: i32,
+}
+
+impl SensorModel for Potentiometer {
+    fn convert(&self, code: u16) -> Reading {
+        let full_scale = i64::from(self.resolution.full_scale());
+        Reading::valid(scale(
+            i64::from(code),
+            (0, full_scale - 1),
+            (self.low, self.high),
+        ))
+    }
+}
+
+impl SensorModel for PiecewiseLinear<'_> {
+    fn convert(&self, code: u16) -> Reading {
+        if let Some(value) = self.evaluate(code) {
+            return Reading::valid(value);
+        }
+        let below = self.points().first().is_some_and(|first| code < first.code);
+        Reading {
+            value: self.evaluate_clamped(code).unwrap_or(0),
+            validity: if below {
+                Validity::UnderRange
+            } else {
+                Validity::OverRange
+            },
+        }
+    }
+}
+
+/// Readings are in centi-degrees Celsius.
+impl SensorModel for Ntc<'_> {
+    fn convert(&self, code: u16) -> Reading {
+        match self.centi_celsius(code) {
+            Ok(centi_celsius) => Reading::valid(centi_celsius),
+            Err(fault) => Reading {
+                value: 0,
+                validity: match fault {
+                    NtcFault::Open | NtcFault::Short => Validity::Fault,
+                    NtcFault::BelowRange => Validity::UnderRange,
+                    NtcFault::AboveRange => Validity::OverRange,
+                },
+            },
+        }
+    }
+}
+
+/// A sensor model for each channel.
+#[derive(Clone, Copy, Default)]
+pub struct ChannelModels<'a> {
+    models: [Option<&'a dyn SensorModel>; 8],
+}
+
+impl<'a> ChannelModels<'a> {
+    /// Creates a set with no models attached.
+    #[must_use]
+    pub const fn new() -> Self {
+        ChannelModels { models: [None; 8] }
+    }
+
+    pub fn attach(&mut self, channel: Channel, model: &'a dyn SensorModel) {
+        if let Some(slot) = self.models.get_mut(channel.index()) {
+            *slot = Some(model);
+        }
+    }
+
+    pub fn detach(&mut self, channel: Channel) {
+        if let Some(slot) = self.models.get_mut(channel.index()) {
+            *slot = None;
+        }
+    }
+
+    /// Converts a result of one channel, or returns `None` if no model is attached.
+    #[must_use]
+    pub fn convert(&self, channel: Channel, code: u16) -> Option<Reading> {
+        self.models
+            .get(channel.index())
+            .copied()
+            .flatten()
+            .map(|model| model.convert(code))
+    }
+
+    /// Converts a frame of results indexed by channel.
+    #[must_use]
+    pub fn convert_frame(&self, frame: &[u16; 8]) -> [Option<Reading>; 8] {
+        let mut readings = [None; 8];
+        for ((reading, model), &code) in readings.iter_mut().zip(&self.models).zip(frame) {
+            *reading = model.map(|sensor| sensor.convert(code));
+        }
+        readings
+    }
+}
+
+impl core::fmt::Debug for ChannelModels<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut list = f.debug_list();
+        for model in &self.models {
+            list.entry(&model.map(|_| "SensorModel"));
+        }
+        list.finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided methods of defmt::Format are internal to defmt."
+)]
+impl defmt::Format for ChannelModels<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let attached = self.models.map(|model| model.is_some());
+        defmt::write!(f, "ChannelModels({=[?]})", attached);
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{
+        ChannelModels, CurrentLoop, Potentiometer, Ratiometric, Reading, SensorModel, Validity,
+        VoltageDivider,
+    };
+    use crate::{
+        channel::Channel,
+        chip_definitions::Resolution,
+        lookup::{Breakpoint, PiecewiseLinear},
+        ntc::{Divider, Ntc, NTC_10K_B3950},
+    };
+
+    /// 62.5 uV per code at 16 bits, so each code is 0.625 uA through a 100 ohm shunt.
+    const LOOP: CurrentLoop = CurrentLoop {
+        shunt_milliohms: 100_000,
+        avdd_microvolts: 4_096_000,
+        resolution: Resolution::Bits16,
+        low: 0,
+        high: 1000,
+    };
+
+    /// The code for a loop current in microamps.
+    const fn loop_code(microamps: u16) -> u16 {
+        microamps / 5 * 8
+    }
+
+    fn reading(value: i32, validity: Validity) -> Reading {
+        Reading { value, validity }
+    }
+
+    #[test]
+    fn current_loop_scales_4_to_20_ma() {
+        assert_eq!(LOOP.microamps(loop_code(12_000)), 12_000);
+        assert_eq!(LOOP.convert(loop_code(4_000)), Reading::valid(0));
+        assert_eq!(LOOP.convert(loop_code(12_000)), Reading::valid(500));
+        assert_eq!(LOOP.convert(loop_code(20_000)), Reading::valid(1000));
+    }
+
+    #[test]
+    fn current_loop_follows_the_namur_limits() {
+        let validity = |microamps| LOOP.convert(loop_code(microamps)).validity;
+        assert_eq!(validity(3_600), Validity::Fault);
+        assert_eq!(validity(3_610), Validity::UnderRange);
+        assert_eq!(validity(3_790), Validity::UnderRange);
+        assert_eq!(validity(3_800), Validity::Valid);
+        assert_eq!(validity(20_500), Validity::Valid);
+        assert_eq!(validity(20_510), Validity::OverRange);
+        assert_eq!(validity(20_990), Validity::OverRange);
+        assert_eq!(validity(21_000), Validity::Fault);
+        assert_eq!(LOOP.convert(0), reading(-250, Validity::Fault));
+        // A broken loop still reports the scaled value as a best effort
+        assert_eq!(
+            LOOP.convert(loop_code(3_700)),
+            reading(-19, Validity::UnderRange)
+        );
+    }
+
+    #[test]
+    fn ratiometric_is_valid_between_8_and_92_percent() {
+        let transducer = Ratiometric {
+            resolution: Resolution::Bits12,
+            low: 0,
+            high: 10_000,
+        };
+        // 10 % and 90 % of 4096 codes
+        assert_eq!(transducer.convert(409), Reading::valid(0));
+        assert_eq!(transducer.convert(3686), Reading::valid(10_000));
+        assert_eq!(transducer.convert(2048), Reading::valid(5002));
+        assert_eq!(transducer.convert(328), Reading::valid(-247));
+        assert_eq!(transducer.convert(327).validity, Validity::UnderRange);
+        assert_eq!(transducer.convert(3772).validity, Validity::Valid);
+        assert_eq!(transducer.convert(3773).validity, Validity::OverRange);
+        assert_eq!(transducer.convert(0).validity, Validity::UnderRange);
+    }
+
+    #[test]
+    fn voltage_divider_reports_the_top_voltage() {
+        let divider = VoltageDivider {
+            top_ohms: 30_000,
+            bottom_ohms: 10_000,
+            avdd_microvolts: 4_096_000,
+            resolution: Resolution::Bits12,
+        };
+        assert_eq!(divider.convert(1000), Reading::valid(4000));
+        assert_eq!(divider.convert(0), Reading::valid(0));
+        assert_eq!(divider.convert(4094), Reading::valid(16_376));
+        assert_eq!(divider.convert(4095), reading(16_380, Validity::OverRange));
+    }
+
+    #[test]
+    fn potentiometer_spans_the_track() {
+        let angle = Potentiometer {
+            resolution: Resolution::Bits12,
+            low: -1800,
+            high: 1800,
+        };
+        assert_eq!(angle.convert(0), Reading::valid(-1800));
+        assert_eq!(angle.convert(2048), Reading::valid(0));
+        assert_eq!(angle.convert(4095), Reading::valid(1800));
+    }
+
+    #[test]
+    fn tables_and_thermistors_report_their_faults() {
+        let points = [Breakpoint::new(100, 0), Breakpoint::new(200, 1000)];
+        let table = PiecewiseLinear::new(&points);
+        assert_eq!(table.convert(150), Reading::valid(500));
+        assert_eq!(table.convert(50), reading(0, Validity::UnderRange));
+        assert_eq!(table.convert(250), reading(1000, Validity::OverRange));
+        let ntc = Ntc::new(
+            NTC_10K_B3950,
+            Divider::PullUp { ohms: 10_000 },
+            Resolution::Bits12,
+        );
+        assert_eq!(ntc.convert(2048), Reading::valid(2500));
+        assert_eq!(ntc.convert(4095).validity, Validity::Fault);
+        assert_eq!(ntc.convert(0).validity, Validity::Fault);
+        assert_eq!(ntc.convert(100).validity, Validity::OverRange);
+    }
+
+    #[test]
+    fn models_are_attached_per_channel() {
+        let angle = Potentiometer {
+            resolution: Resolution::Bits12,
+            low: 0,
+            high: 4095,
+        };
+        let mut models = ChannelModels::new();
+        models.attach(Channel::Channel1, &LOOP);
+        models.attach(Channel::Channel4, &angle);
+        models.attach(Channel::Channel6, &angle);
+        models.detach(Channel::Channel6);
+        assert_eq!(models.convert(Channel::Channel0, 100), None);
+        assert_eq!(
+            models.convert_frame(&[0, loop_code(20_000), 0, 0, 123, 0, 123, 0]),
+            [
+                None,
+                Some(Reading::valid(1000)),
+                None,
+                None,
+                Some(Reading::valid(123)),
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,966 @@
+//! Simulated TLA2528 for host-side testing.
+//!
+//! `SimulatedTla2528` implements `embedded_hal::i2c::I2c` and models the register file, the
+//! opcode protocol, manual and auto-sequence conversions with optional channel IDs,
+//! oversampling and the GPIO pins, so code built on `Tla2528` can be exercised without
+//! hardware. Pass `&mut sim` to `Tla2528::new()` to keep access to the model during a test.
+//!
+//! Each analog input either converts a fixed code, is driven by an `AnalogSource`, a
+//! waveform over simulated time that is quantised against AVDD to a 12-bit code, or floats and
+//! holds the level its pin last drove as a push-pull output. Simulated
+//! time advances by one sampling period, as set through `OPMODE_CFG`, per conversion, so
+//! oversampling averages consecutive points of the waveform as the device does.
+//!
+//! Faults can be injected to exercise error handling: failed bus transactions, brownout
+//! resets, a calibration bit that never clears, corrupted channel IDs and out-of-order
+//! auto-sequence frames.
+
+use core::fmt;
+
+use embedded_hal::i2c::{
+    ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress,
+};
+
+use crate::{
+    channel::Channel,
+    chip_definitions::{GeneralConfigFlags, OpCode, RegisterAddress, SystemStatusFlags},
+};
+
+const REGISTER_COUNT: usize = 0x13;
+const SYSTEM_STATUS_RESET_VALUE: u8 = 0b_1000_0001;
+const FULL_SCALE_CODE: u16 = 0x0FFF;
+const FIXED_PATTERN_CODE: u16 = 0x0A5A;
+const FIXED_PATTERN_AVERAGED: u16 = 0xA5A5;
+
+const DATA_CONFIG_FIXED_PATTERN: u8 = 0b_1000_0000;
+const DATA_CONFIG_APPEND_MASK: u8 = 0b_0011_0000;
+const DATA_CONFIG_APPEND_CHANNEL_ID: u8 = 0b_0001_0000;
+const SEQUENCE_CONFIG_MODE_MASK: u8 = 0b_0000_0011;
+const SEQUENCE_CONFIG_MODE_AUTO: u8 = 0b_0000_0001;
+const SEQUENCE_CONFIG_START: u8 = 0b_0001_0000;
+const OSR_CONFIG_MASK: u8 = 0b_0000_0111;
+const CHANNEL_SELECT_MASK: u8 = 0b_0000_1111;
+const OPMODE_CONFIG_LOW_SPEED_OSCILLATOR: u8 = 0b_0001_0000;
+const OPMODE_CONFIG_CLOCK_DIVIDER_MASK: u8 = 0b_0000_1111;
+
+const DEFAULT_AVDD_MICROVOLTS: u32 = 3_300_000;
+const HIGH_SPEED_BASE_PERIOD_NS: u64 = 1_000;
+const LOW_SPEED_BASE_PERIOD_NS: u64 = 32_000;
+/// Sampling period multipliers for each `CLK_DIV` setting, doubled to keep them integral.
+const CLOCK_DIVIDER_TIMES_TWO: [u64; 16] = [
+    2, 3, 4, 6, 8, 12, 16, 24, 32, 48, 64, 96, 128, 192, 256, 384,
+];
+
+/// A signal applied to a simulated analog input.
+pub trait AnalogSource {
+    /// The input voltage in microvolts at `time_ns` nanoseconds of simulated time.
+    fn microvolts(&mut self, time_ns: u64) -> u32;
+}
+
+/// Any `FnMut(u64) -> u32` closure over simulated time can drive an input.
+impl<F> AnalogSource for F
+where
+    F: FnMut(u64) -> u32,
+{
+    fn microvolts(&mut self, time_ns: u64) -> u32 {
+        self(time_ns)
+    }
+}
+
+/// A fixed input voltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Constant {
+    pub microvolts: u32,
+}
+
+impl AnalogSource for Constant {
+    fn microvolts(&mut self, _time_ns: u64) -> u32 {
+        self.microvolts
+    }
+}
+
+/// A linear ramp starting at `start_microvolts`, clamped at 0 V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ramp {
+    pub start_microvolts: u32,
+    pub microvolts_per_ms: i32,
+}
+
+impl AnalogSource for Ramp {
+    fn microvolts(&mut self, time_ns: u64) -> u32 {
+        let delta = i128::from(self.microvolts_per_ms) * i128::from(time_ns) / 1_000_000;
+        let level = i128::from(self.start_microvolts) + delta;
+        u32::try_from(level.max(0)).unwrap_or(u32::MAX)
+    }
+}
+
+/// A periodic waveform given as one cycle of equally spaced samples, such as a sine table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WaveTable<'a> {
+    pub table: &'a [u32],
+    pub period_ns: u64,
+}
+
+impl AnalogSource for WaveTable<'_> {
+    fn microvolts(&mut self, time_ns: u64) -> u32 {
+        if self.table.is_empty() || self.period_ns == 0 {
+            return 0;
+        }
+        let phase = time_ns % self.period_ns;
+        let index = phase * self.table.len() as u64 / self.period_ns;
+        self.table
+            .get(usize::try_from(index).unwrap_or(0))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// Uniform pseudo-random noise of +/- `amplitude_microvolts` around `center_microvolts`.
+/// The sequence is deterministic for a given seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Noise {
+    center_microvolts: u32,
+    amplitude_microvolts: u32,
+    state: u32,
+}
+
+impl Noise {
+    #[must_use]
+    pub fn new(center_microvolts: u32, amplitude_microvolts: u32, seed: u32) -> Self {
+        Noise {
+            center_microvolts,
+            amplitude_microvolts,
+            // xorshift must not be seeded with zero
+            state: seed.max(1),
+        }
+    }
+}
+
+impl AnalogSource for Noise {
+    fn microvolts(&mut self, _time_ns: u64) -> u32 {
+        self.state ^= self.state << 13_u32;
+        self.state ^= self.state >> 17_u32;
+        self.state ^= self.state << 5_u32;
+        let span = u64::from(self.amplitude_microvolts) * 2 + 1;
+        let offset = u64::from(self.state) % span;
+        let level = u64::from(self.center_microvolts) + offset;
+        u32::try_from(level.saturating_sub(u64::from(self.amplitude_microvolts)))
+            .unwrap_or(u32::MAX)
+    }
+}
+
+enum Input<'a> {
+    Code(u16),
+    Source(&'a mut dyn AnalogSource),
+    Floating,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BusErrorFault {
+    error: ErrorKind,
+    skip: u32,
+    count: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Faults {
+    bus_error: Option<BusErrorFault>,
+    calibration_stuck: bool,
+    channel_id: Option<u8>,
+    reversed_sequence: bool,
+    brownout_after: Option<u32>,
+}
+
+impl Faults {
+    fn next_bus_error(&mut self) -> Option<ErrorKind> {
+        let fault = self.bus_error.as_mut()?;
+        if fault.skip > 0 {
+            fault.skip -= 1;
+            return None;
+        }
+        let error = fault.error;
+        fault.count -= 1;
+        if fault.count == 0 {
+            self.bus_error = None;
+        }
+        Some(error)
+    }
+
+    fn brownout_due(&mut self) -> bool {
+        match self.brownout_after {
+            Some(0) => {
+                self.brownout_after = None;
+                true
+            }
+            Some(ref mut skip) => {
+                *skip -= 1;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct SimulatedTla2528<'a> {
+    address: u8,
+    registers: [u8; REGISTER_COUNT],
+    inputs: [Input<'a>; 8],
+    avdd_microvolts: u32,
+    time_ns: u64,
+    digital_inputs: u8,
+    /// Level each floating input holds, from when its pin last drove one.
+    held_levels: u8,
+    register_pointer: Option<u8>,
+    sequence_position: u8,
+    high_speed_host: bool,
+    faults: Faults,
+}
+
+impl<'a> SimulatedTla2528<'a> {
+    /// Creates a device in its power-on state, answering on `address`.
+    #[must_use]
+    pub fn new(address: u8) -> Self {
+        let mut sim = SimulatedTla2528 {
+            address,
+            registers: [0_u8; REGISTER_COUNT],
+            inputs: [const { Input::Code(0) }; 8],
+            avdd_microvolts: DEFAULT_AVDD_MICROVOLTS,
+            time_ns: 0,
+            digital_inputs: 0,
+            held_levels: 0,
+            register_pointer: None,
+            sequence_position: 0,
+            high_speed_host: false,
+            faults: Faults::default(),
+        };
+        sim.reset();
+        sim
+    }
+
+    /// Sets the 12-bit code an analog input converts to. Values above full scale saturate.
+    pub fn set_input(&mut self, channel: Channel, code: u16) {
+        self.inputs[channel as usize] = Input::Code(code.min(FULL_SCALE_CODE));
+    }
+
+    /// Drives an analog input from `source` in place of a fixed code.
+    pub fn attach_source(&mut self, channel: Channel, source: &'a mut dyn AnalogSource) {
+        self.inputs[channel as usize] = Input::Source(source);
+    }
+
+    /// Leaves an analog input unconnected, as an open sensor wire does. It converts to the
+    /// level its pin last drove as a push-pull output, full scale after driving high and 0
+    /// after driving low, and to 0 if the pin has not driven since the simulation started.
+    pub fn set_floating(&mut self, channel: Channel) {
+        self.inputs[channel as usize] = Input::Floating;
+    }
+
+    /// Sets the supply voltage that source levels are quantised against. Defaults to 3.3 V.
+    pub fn set_avdd(&mut self, microvolts: u32) {
+        self.avdd_microvolts = microvolts.max(1);
+    }
+
+    /// Advances simulated time, as when the host waits between transactions.
+    pub fn advance_time(&mut self, nanoseconds: u64) {
+        self.time_ns += nanoseconds;
+    }
+
+    /// Simulated time in nanoseconds.
+    #[must_use]
+    pub fn time_ns(&self) -> u64 {
+        self.time_ns
+    }
+
+    /// Sets the level seen by a pin configured as a digital input.
+    pub fn set_digital_input(&mut self, channel: Channel, high: bool) {
+        let mask = 1 << channel as u8;
+        if high {
+            self.digital_inputs |= mask;
+        } else {
+            self.digital_inputs &= !mask;
+        }
+    }
+
+    /// The value the host would read back from `register`.
+    #[must_use]
+    pub fn register(&self, register: RegisterAddress) -> u8 {
+        self.load_register(register.value())
+    }
+
+    /// The level driven by a pin configured as a digital output, or `None` for other pins.
+    #[must_use]
+    pub fn output_level(&self, channel: Channel) -> Option<bool> {
+        let mask = 1 << channel as u8;
+        let is_output = self.stored(RegisterAddress::PinConfig)
+            & self.stored(RegisterAddress::GpioConfig)
+            & mask
+            != 0;
+        is_output.then(|| self.stored(RegisterAddress::GpOutValue) & mask != 0)
+    }
+
+    /// When `enabled`, models a host whose HAL sends the HS-mode master code and a repeated
+    /// START ahead of every transaction, so the device reports
+    /// `SystemStatusFlags::I2C_HIGH_SPEED` until the STOP that ends each one.
+    pub fn set_high_speed_host(&mut self, enabled: bool) {
+        self.high_speed_host = enabled;
+    }
+
+    /// Fails `count` transactions with `error` after letting the next `skip` transactions
+    /// through. NACKs, bus errors and arbitration loss are all expressed as an `ErrorKind`.
+    pub fn inject_bus_error(&mut self, error: ErrorKind, skip: u32, count: u32) {
+        self.faults.bus_error = (count > 0).then_some(BusErrorFault { error, skip, count });
+    }
+
+    /// Simulates a supply brownout: all registers return to their reset values and
+    /// `SystemStatusFlags::BROWNOUT_RESET` is set.
+    pub fn brownout(&mut self) {
+        self.reset();
+    }
+
+    /// Browns the device out, as `brownout()` does, ahead of the transaction that follows the
+    /// next `skip` transactions. Used to reset the device under a driver that holds the bus.
+    pub fn inject_brownout(&mut self, skip: u32) {
+        self.faults.brownout_after = Some(skip);
+    }
+
+    /// When `stuck`, a requested offset calibration never completes and
+    /// `GeneralConfigFlags::CALIBRATE_ADC_OFFSET` stays set.
+    pub fn set_calibration_stuck(&mut self, stuck: bool) {
+        self.faults.calibration_stuck = stuck;
+    }
+
+    /// Replaces the channel ID appended to every conversion frame with the raw 4-bit `id`.
+    /// IDs above 7 are invalid; valid IDs misreport which channel was converted.
+    pub fn corrupt_channel_ids(&mut self, id: Option<u8>) {
+        self.faults.channel_id = id.map(|raw| raw & CHANNEL_SELECT_MASK);
+    }
+
+    /// When `reversed`, the auto-sequence walks the enabled channels from highest to lowest.
+    pub fn reorder_sequence(&mut self, reversed: bool) {
+        self.faults.reversed_sequence = reversed;
+    }
+
+    /// Removes all injected faults.
+    pub fn clear_faults(&mut self) {
+        self.faults = Faults::default();
+    }
+
+    fn reset(&mut self) {
+        self.registers = [0_u8; REGISTER_COUNT];
+        self.registers[RegisterAddress::SystemStatus as usize] = SYSTEM_STATUS_RESET_VALUE;
+        self.register_pointer = None;
+        self.sequence_position = 0;
+    }
+
+    fn stored(&self, register: RegisterAddress) -> u8 {
+        self.registers[register as usize]
+    }
+
+    fn load_register(&self, register: u8) -> u8 {
+        match register {
+            x if x == RegisterAddress::SystemStatus.value() => {
+                let mut status =
+                    SystemStatusFlags::from_bits_retain(self.stored(RegisterAddress::SystemStatus));
+                status.set(
+                    SystemStatusFlags::SEQUENCER_IN_PROGRESS,
+                    self.sequence_running(),
+                );
+                status.bits()
+            }
+            x if x == RegisterAddress::GpInValue.value() => {
+                let gpio = self.stored(RegisterAddress::PinConfig);
+                let outputs = gpio & self.stored(RegisterAddress::GpioConfig);
+                let inputs = gpio & !outputs;
+                (self.digital_inputs & inputs)
+                    | (self.stored(RegisterAddress::GpOutValue) & outputs)
+            }
+            x => self.registers.get(x as usize).copied().unwrap_or(0),
+        }
+    }
+
+    fn store_register(&mut self, register: u8, value: u8) {
+        match register {
+            x if x == RegisterAddress::SystemStatus.value() => {
+                // BOR is cleared by writing 1, the remaining bits are read-only
+                let clear = value & SystemStatusFlags::BROWNOUT_RESET.bits();
+                self.registers[x as usize] &= !clear;
+            }
+            x if x == RegisterAddress::GeneralConfig.value() => {
+                let config = GeneralConfigFlags::from_bits_retain(value);
+                if config.contains(GeneralConfigFlags::RESET) {
+                    self.reset();
+                    return;
+                }
+                // Calibration and conversion start complete immediately and self-clear
+                let mut completed = GeneralConfigFlags::INITIATE_CONVERSION;
+                if !self.faults.calibration_stuck {
+                    completed |= GeneralConfigFlags::CALIBRATE_ADC_OFFSET;
+                }
+                self.registers[x as usize] = (config - completed).bits();
+            }
+            x if x == RegisterAddress::GpInValue.value() => {}
+            x if x == RegisterAddress::SequenceConfig.value() => {
+                if value & SEQUENCE_CONFIG_START != 0 {
+                    self.sequence_position = 0;
+                }
+                self.registers[x as usize] = value;
+            }
+            x => {
+                if let Some(stored) = self.registers.get_mut(x as usize) {
+                    *stored = value;
+                }
+                self.latch_driven_levels();
+            }
+        }
+    }
+
+    /// Charges floating inputs whose pins drive high as push-pull outputs and discharges those
+    /// whose pins drive low. Open-drain outputs only pull low.
+    fn latch_driven_levels(&mut self) {
+        let outputs =
+            self.stored(RegisterAddress::PinConfig) & self.stored(RegisterAddress::GpioConfig);
+        let levels = self.stored(RegisterAddress::GpOutValue);
+        let high = outputs & self.stored(RegisterAddress::GpioDriveConfig) & levels;
+        let low = outputs & !levels;
+        self.held_levels = (self.held_levels | high) & !low;
+    }
+
+    fn handle_command(&mut self, bytes: &[u8]) {
+        self.register_pointer = None;
+        let [opcode, register, ref data @ ..] = *bytes else {
+            return;
+        };
+        match OpCode::try_from(opcode) {
+            Ok(OpCode::SingleRegisterRead | OpCode::ReadContinuousRegisters) => {
+                self.register_pointer = Some(register);
+            }
+            Ok(OpCode::SingleRegisterWrite) => {
+                if let Some(&value) = data.first() {
+                    self.store_register(register, value);
+                }
+            }
+            Ok(OpCode::WriteContinuousRegisters) => {
+                for (destination, &value) in (register..=u8::MAX).zip(data) {
+                    self.store_register(destination, value);
+                }
+            }
+            Ok(OpCode::SetBit) => {
+                if let Some(&mask) = data.first() {
+                    self.store_register(register, self.load_register(register) | mask);
+                }
+            }
+            Ok(OpCode::ClearBit) => {
+                if let Some(&mask) = data.first() {
+                    self.store_register(register, self.load_register(register) & !mask);
+                }
+            }
+            // The device ignores invalid opcodes
+            Err(()) => {}
+        }
+    }
+
+    fn handle_read(&mut self, buffer: &mut [u8]) {
+        if let Some(register) = self.register_pointer.take() {
+            for (source, byte) in (register..=u8::MAX).zip(buffer.iter_mut()) {
+                *byte = self.load_register(source);
+            }
+            return;
+        }
+
+        let mut frame = [0_u8; 3];
+        let mut frame_len = 0;
+        let mut frame_index = 0;
+        for byte in buffer.iter_mut() {
+            if frame_index == frame_len {
+                (frame, frame_len) = self.conversion_frame();
+                frame_index = 0;
+            }
+            *byte = frame[frame_index];
+            frame_index += 1;
+        }
+    }
+
+    fn sequence_running(&self) -> bool {
+        let config = self.stored(RegisterAddress::SequenceConfig);
+        config & SEQUENCE_CONFIG_MODE_MASK == SEQUENCE_CONFIG_MODE_AUTO
+            && config & SEQUENCE_CONFIG_START != 0
+    }
+
+    fn next_channel(&mut self) -> Channel {
+        if self.sequence_running() {
+            let enabled = self.stored(RegisterAddress::AutoSequenceChannelSelect);
+            for _ in 0..8 {
+                let candidate = if self.faults.reversed_sequence {
+                    7 - self.sequence_position
+                } else {
+                    self.sequence_position
+                };
+                self.sequence_position = (self.sequence_position + 1) % 8;
+                if enabled & (1 << candidate) != 0 {
+                    return Channel::try_from(candidate).unwrap_or(Channel::Channel0);
+                }
+            }
+            Channel::Channel0
+        } else {
+            let selected = self.stored(RegisterAddress::ChannelSelect) & CHANNEL_SELECT_MASK;
+            Channel::try_from(selected).unwrap_or(Channel::Channel0)
+        }
+    }
+
+    fn sampling_period_ns(&self) -> u64 {
+        let config = self.stored(RegisterAddress::OpModeConfig);
+        let base = if config & OPMODE_CONFIG_LOW_SPEED_OSCILLATOR == 0 {
+            HIGH_SPEED_BASE_PERIOD_NS
+        } else {
+            LOW_SPEED_BASE_PERIOD_NS
+        };
+        let divider = CLOCK_DIVIDER_TIMES_TWO[(config & OPMODE_CONFIG_CLOCK_DIVIDER_MASK) as usize];
+        base * divider / 2
+    }
+
+    fn quantise(&self, microvolts: u32) -> u16 {
+        let code = u64::from(microvolts) * (u64::from(FULL_SCALE_CODE) + 1)
+            / u64::from(self.avdd_microvolts);
+        u16::try_from(code.min(u64::from(FULL_SCALE_CODE))).unwrap_or(FULL_SCALE_CODE)
+    }
+
+    /// Converts one sample of `channel` to a 12-bit code and advances simulated time.
+    fn sample(&mut self, channel: Channel) -> u16 {
+        let time_ns = self.time_ns;
+        self.time_ns += self.sampling_period_ns();
+        match self.output_level(channel) {
+            Some(true)
+                if self.stored(RegisterAddress::GpioDriveConfig) & (1 << channel as u8) != 0 =>
+            {
+                FULL_SCALE_CODE
+            }
+            Some(false) => 0,
+            Some(true) | None => match self.inputs[channel as usize] {
+                Input::Code(code) => code,
+                Input::Source(ref mut source) => {
+                    let microvolts = source.microvolts(time_ns);
+                    self.quantise(microvolts)
+                }
+                Input::Floating if self.held_levels & (1 << channel as u8) != 0 => FULL_SCALE_CODE,
+                Input::Floating => 0,
+            },
+        }
+    }
+
+    /// Produces the next conversion frame and the number of bytes it occupies on the bus.
+    fn conversion_frame(&mut self) -> ([u8; 3], usize) {
+        let channel = self.next_channel();
+        let data_config = self.stored(RegisterAddress::DataConfig);
+        let fixed_pattern = data_config & DATA_CONFIG_FIXED_PATTERN != 0;
+        let channel_id = (data_config & DATA_CONFIG_APPEND_MASK == DATA_CONFIG_APPEND_CHANNEL_ID)
+            .then_some(channel as u8)
+            .map(|id| self.faults.channel_id.unwrap_or(id));
+
+        let ratio_log2 = self.stored(RegisterAddress::OsrConfig) & OSR_CONFIG_MASK;
+        if ratio_log2 == 0 {
+            let code = if fixed_pattern {
+                FIXED_PATTERN_CODE
+            } else {
+                self.sample(channel)
+            };
+            let [high, low] = ((code << 4) | u16::from(channel_id.unwrap_or(0))).to_be_bytes();
+            ([high, low, 0], 2)
+        } else {
+            let count = 1_u32 << ratio_log2;
+            let sum: u32 = (0..count).map(|_| u32::from(self.sample(channel))).sum();
+            let average = if fixed_pattern {
+                FIXED_PATTERN_AVERAGED
+            } else {
+                u16::try_from((sum << 4) / count).unwrap_or(u16::MAX)
+            };
+            let [high, low] = average.to_be_bytes();
+            match channel_id {
+                Some(id) => ([high, low, id << 4], 3),
+                None => ([high, low, 0], 2),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SimulatedTla2528<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedTla2528")
+            .field("address", &self.address)
+            .field("registers", &self.registers)
+            .field("time_ns", &self.time_ns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ErrorType for SimulatedTla2528<'_> {
+    type Error = ErrorKind;
+}
+
+impl I2c<SevenBitAddress> for SimulatedTla2528<'_> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if self.faults.brownout_due() {
+            self.reset();
+        }
+        if let Some(error) = self.faults.next_bus_error() {
+            return Err(error);
+        }
+        // A master code sent on its own is followed by STOP, which leaves high-speed mode again
+        if address != self.address {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        let high_speed = SystemStatusFlags::I2C_HIGH_SPEED.bits();
+        if self.high_speed_host {
+            self.registers[RegisterAddress::SystemStatus as usize] |= high_speed;
+        }
+        for operation in operations.iter_mut() {
+            match *operation {
+                Operation::Write(bytes) => self.handle_command(bytes),
+                Operation::Read(ref mut buffer) => self.handle_read(buffer),
+            }
+        }
+        self.registers[RegisterAddress::SystemStatus as usize] &= !high_speed;
+        Ok(())
+    }
+
+    fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Read(read)])
+    }
+
+    fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(address, &mut [Operation::Write(write)])
+    }
+
+    fn write_read(
+        &mut self,
+        address: SevenBitAddress,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.transaction(
+            address,
+            &mut [Operation::Write(write), Operation::Read(read)],
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+
+    use super::{Constant, Noise, Ramp, SimulatedTla2528, WaveTable};
+    use crate::{
+        channel::Channel,
+        chip_definitions::{Oversampling, PinMode, RegisterAddress, SystemStatusFlags},
+        error::{Error, Operation},
+        Tla2528,
+    };
+
+    const ADDRESS: u8 = 0x10;
+    /// AVDD at which one 12-bit code is one millivolt.
+    const MILLIVOLT_PER_CODE_AVDD: u32 = 4_096_000;
+
+    #[test]
+    fn invalid_channel_ids_are_rejected() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.corrupt_channel_ids(Some(0x0F));
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        assert!(matches!(
+            adc.acquire_channel_data(Channel::Channel3),
+            Err(Error::InvalidChannelAddress)
+        ));
+        adc.set_oversampling_ratio(Oversampling::Ratio4).unwrap();
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        assert!(matches!(
+            adc.acquire_data(),
+            Err(Error::InvalidChannelAddress)
+        ));
+    }
+
+    #[test]
+    fn wrong_channel_ids_are_rejected() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.corrupt_channel_ids(Some(2));
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        assert!(matches!(
+            adc.acquire_channel_data(Channel::Channel3),
+            Err(Error::IncorrectChannelAddress)
+        ));
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        assert!(matches!(
+            adc.acquire_data(),
+            Err(Error::DataItemsMisOrdered)
+        ));
+    }
+
+    #[test]
+    fn reordered_sequence_is_rejected() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.reorder_sequence(true);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_auto_sequence_mode().unwrap();
+        assert!(matches!(
+            adc.acquire_data(),
+            Err(Error::DataItemsMisOrdered)
+        ));
+    }
+
+    #[test]
+    fn stuck_calibration_times_out() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_calibration_stuck(true);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        assert!(matches!(adc.calibrate(), Err(Error::CalibrationTimeout)));
+    }
+
+    #[test]
+    fn bus_errors_carry_the_failed_operation() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.inject_bus_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address), 0, 1);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        assert!(matches!(
+            adc.get_system_status(),
+            Err(Error::Bus {
+                operation: Operation::RegisterRead(RegisterAddress::SystemStatus),
+                source: ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            })
+        ));
+        assert!(adc.get_system_status().is_ok());
+
+        sim.inject_bus_error(ErrorKind::ArbitrationLoss, 0, 2);
+        let mut writer = Tla2528::new(&mut sim, ADDRESS);
+        for _ in 0..2_u32 {
+            assert!(matches!(
+                writer.set_oversampling_ratio(Oversampling::Ratio2),
+                Err(Error::Bus {
+                    operation: Operation::RegisterWrite(RegisterAddress::OsrConfig),
+                    source: ErrorKind::ArbitrationLoss,
+                })
+            ));
+        }
+        writer.set_oversampling_ratio(Oversampling::Ratio2).unwrap();
+        writer.prepare_for_manual_mode().unwrap();
+
+        // The channel select write goes through, the data read fails
+        sim.inject_bus_error(ErrorKind::Bus, 1, 1);
+        let mut reader = Tla2528::new(&mut sim, ADDRESS);
+        assert!(matches!(
+            reader.acquire_channel_data(Channel::Channel1),
+            Err(Error::Bus {
+                operation: Operation::DataRead,
+                source: ErrorKind::Bus,
+            })
+        ));
+    }
+
+    #[test]
+    fn brownout_resets_the_configuration() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_input(Channel::Channel3, 0x321);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.write_register(
+            RegisterAddress::SystemStatus,
+            SystemStatusFlags::BROWNOUT_RESET.bits(),
+        )
+        .unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x321);
+        assert!(!adc
+            .get_system_status()
+            .unwrap()
+            .contains(SystemStatusFlags::BROWNOUT_RESET));
+
+        sim.brownout();
+        let mut recovered = Tla2528::new(&mut sim, ADDRESS);
+        assert!(recovered
+            .get_system_status()
+            .unwrap()
+            .contains(SystemStatusFlags::BROWNOUT_RESET));
+        // Channel IDs are no longer appended, so every frame reads as channel 0
+        assert!(matches!(
+            recovered.acquire_channel_data(Channel::Channel3),
+            Err(Error::IncorrectChannelAddress)
+        ));
+        recovered.prepare_for_manual_mode().unwrap();
+        assert_eq!(
+            recovered.acquire_channel_data(Channel::Channel3).unwrap(),
+            0x321
+        );
+    }
+
+    #[test]
+    fn brownout_with_oversampling_restores_2_byte_frames() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_input(Channel::Channel3, 0x321);
+        // Oversampling, manual mode and one conversion take 9 transactions
+        sim.inject_brownout(9);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio16).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x3210);
+
+        assert!(adc
+            .get_system_status()
+            .unwrap()
+            .contains(SystemStatusFlags::BROWNOUT_RESET));
+        adc.prepare_for_manual_mode().unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x321);
+        assert_eq!(sim.register(RegisterAddress::OsrConfig), 0);
+    }
+
+    /// Converts `count` samples of `channel` in manual mode, one conversion each.
+    fn acquire(sim: &mut SimulatedTla2528<'_>, channel: Channel, count: usize) -> Vec<u16> {
+        let mut adc = Tla2528::new(sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        (0..count)
+            .map(|_| adc.acquire_channel_data(channel).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn ramp_follows_simulated_time() {
+        let mut ramp = Ramp {
+            start_microvolts: 1_000_000,
+            microvolts_per_ms: 100_000,
+        };
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_avdd(MILLIVOLT_PER_CODE_AVDD);
+        sim.attach_source(Channel::Channel0, &mut ramp);
+        assert_eq!(acquire(&mut sim, Channel::Channel0, 1), [1000]);
+        // Each conversion takes 1 us at the default sampling rate
+        assert_eq!(sim.time_ns(), 1_000);
+        sim.advance_time(999_000);
+        assert_eq!(acquire(&mut sim, Channel::Channel0, 1), [1100]);
+        sim.advance_time(4_000_000);
+        assert_eq!(acquire(&mut sim, Channel::Channel0, 1), [1500]);
+    }
+
+    #[test]
+    fn wave_table_wraps_around() {
+        let table = [0_u32, 1_000_000, 2_000_000, 3_000_000];
+        let mut wave = WaveTable {
+            table: &table,
+            period_ns: 4_000,
+        };
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_avdd(MILLIVOLT_PER_CODE_AVDD);
+        sim.attach_source(Channel::Channel1, &mut wave);
+        assert_eq!(
+            acquire(&mut sim, Channel::Channel1, 6),
+            [0, 1000, 2000, 3000, 0, 1000]
+        );
+    }
+
+    #[test]
+    fn noise_stays_within_its_amplitude() {
+        let mut noise = Noise::new(2_048_000, 100_000, 7);
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_avdd(MILLIVOLT_PER_CODE_AVDD);
+        sim.attach_source(Channel::Channel2, &mut noise);
+        let samples = acquire(&mut sim, Channel::Channel2, 200);
+        assert!(samples.iter().all(|code| (1948..=2148).contains(code)));
+        let spread = samples.iter().max().unwrap() - samples.iter().min().unwrap();
+        assert!(spread > 100, "noise spread only {spread} codes");
+    }
+
+    #[test]
+    fn closure_is_driven_by_simulated_time() {
+        // 250 mV per microsecond of simulated time
+        let mut staircase =
+            |time_ns: u64| u32::try_from(time_ns / 1_000 * 250_000).unwrap_or(u32::MAX);
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_avdd(MILLIVOLT_PER_CODE_AVDD);
+        sim.attach_source(Channel::Channel3, &mut staircase);
+        assert_eq!(acquire(&mut sim, Channel::Channel3, 4), [0, 250, 500, 750]);
+    }
+
+    #[test]
+    fn quantisation_clamps_to_the_code_range() {
+        let mut falling = Ramp {
+            start_microvolts: 1_000,
+            microvolts_per_ms: -1_000_000,
+        };
+        let mut at_avdd = Constant {
+            microvolts: 3_300_000,
+        };
+        let mut above_avdd = Constant {
+            microvolts: 5_000_000,
+        };
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.attach_source(Channel::Channel4, &mut falling);
+        sim.attach_source(Channel::Channel5, &mut at_avdd);
+        sim.attach_source(Channel::Channel6, &mut above_avdd);
+        sim.advance_time(1_000_000);
+        assert_eq!(acquire(&mut sim, Channel::Channel4, 1), [0]);
+        assert_eq!(acquire(&mut sim, Channel::Channel5, 1), [0x0FFF]);
+        assert_eq!(acquire(&mut sim, Channel::Channel6, 1), [0x0FFF]);
+    }
+
+    #[test]
+    fn oversampling_averages_the_source_over_time() {
+        // One code per microsecond, starting at 1000
+        let mut rising =
+            |time_ns: u64| u32::try_from(1_000_000 + time_ns / 1_000 * 1_000).unwrap_or(u32::MAX);
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_avdd(MILLIVOLT_PER_CODE_AVDD);
+        sim.attach_source(Channel::Channel7, &mut rising);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.set_oversampling_ratio(Oversampling::Ratio4).unwrap();
+        adc.prepare_for_manual_mode().unwrap();
+        // Means of codes 1000..=1003 and 1004..=1007, as 16-bit results
+        assert_eq!(
+            adc.acquire_channel_data(Channel::Channel7).unwrap(),
+            1001 * 16 + 8
+        );
+        assert_eq!(
+            adc.acquire_channel_data(Channel::Channel7).unwrap(),
+            1005 * 16 + 8
+        );
+        assert_eq!(sim.time_ns(), 8_000);
+    }
+
+    #[test]
+    fn floating_input_holds_the_level_it_was_last_driven_to() {
+        let mut sim = SimulatedTla2528::new(ADDRESS);
+        sim.set_floating(Channel::Channel2);
+        let mut adc = Tla2528::new(&mut sim, ADDRESS);
+        adc.prepare_for_manual_mode().unwrap();
+        let mut drive_then_sample = |mode, high| {
+            adc.set_output(Channel::Channel2, high).unwrap();
+            adc.configure_pin(Channel::Channel2, mode).unwrap();
+            adc.configure_pin(Channel::Channel2, PinMode::AnalogInput)
+                .unwrap();
+            adc.acquire_channel_data(Channel::Channel2).unwrap()
+        };
+        assert_eq!(drive_then_sample(PinMode::AnalogInput, true), 0);
+        assert_eq!(
+            drive_then_sample(PinMode::DigitalOutputPushPull, true),
+            0xFFF
+        );
+        // An open-drain output releases the pin when high, so the charge stays
+        assert_eq!(
+            drive_then_sample(PinMode::DigitalOutputOpenDrain, true),
+            0xFFF
+        );
+        assert_eq!(drive_then_sample(PinMode::DigitalOutputOpenDrain, false), 0);
+        assert_eq!(drive_then_sample(PinMode::DigitalInput, true), 0);
+        assert_eq!(
+            drive_then_sample(PinMode::DigitalOutputPushPull, true),
+            0xFFF
+        );
+        assert_eq!(drive_then_sample(PinMode::DigitalOutputPushPull, false), 0);
+    }
+}
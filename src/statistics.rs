@@ -0,0 +1,157 @@
+//! Per-channel signal statistics.
+//!
+//! `Statistics` tracks the minimum, maximum, mean and sample count of each channel over the
+//! frames it is given, using integer arithmetic only, so diagnostics can report signal ranges.
+
+use crate::{channel::Channel, filter::FrameProcessor};
+
+/// Statistics of one channel since the last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStatistics {
+    min: u16,
+    max: u16,
+    count: u32,
+    sum: u64,
+}
+
+impl ChannelStatistics {
+    /// Adds a sample.
+    pub fn record(&mut self, sample: u16) {
+        if self.count == 0 {
+            self.min = sample;
+            self.max = sample;
+        } else {
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+        }
+        // Once the count saturates the mean covers the first `u32::MAX` samples only, so
+        // `sum` stays consistent with `count` and cannot overflow
+        if let Some(count) = self.count.checked_add(1) {
+            self.count = count;
+            self.sum += u64::from(sample);
+        }
+    }
+
+    /// The smallest sample, or `None` if nothing has been recorded.
+    #[must_use]
+    pub fn min(&self) -> Option<u16> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The largest sample, or `None` if nothing has been recorded.
+    #[must_use]
+    pub fn max(&self) -> Option<u16> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The mean of the samples, rounded to the nearest code, or `None` if nothing has been
+    /// recorded.
+    #[must_use]
+    pub fn mean(&self) -> Option<u16> {
+        let count = u64::from(self.count);
+        (count > 0).then(|| u16::try_from((self.sum + count / 2) / count).unwrap_or(u16::MAX))
+    }
+
+    /// The difference between the largest and smallest samples.
+    #[must_use]
+    pub fn span(&self) -> Option<u16> {
+        (self.count > 0).then(|| self.max - self.min)
+    }
+
+    /// Number of samples recorded, saturating at `u32::MAX`. Later samples still update the
+    /// minimum and maximum but no longer the mean.
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn reset(&mut self) {
+        *self = ChannelStatistics::default();
+    }
+}
+
+/// Statistics for every channel.
+///
+/// As a `FrameProcessor` every frame seen is recorded; frames are not modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statistics {
+    channels: [ChannelStatistics; 8],
+}
+
+impl Statistics {
+    #[must_use]
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    /// Records a frame of results indexed by channel.
+    pub fn record(&mut self, frame: &[u16; 8]) {
+        for (statistics, &sample) in self.channels.iter_mut().zip(frame) {
+            statistics.record(sample);
+        }
+    }
+
+    #[must_use]
+    pub fn channel(&self, channel: Channel) -> ChannelStatistics {
+        self.channels
+            .get(channel.index())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Discards the statistics of every channel.
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(ChannelStatistics::reset);
+    }
+
+    /// Discards the statistics of one channel.
+    pub fn reset_channel(&mut self, channel: Channel) {
+        if let Some(statistics) = self.channels.get_mut(channel.index()) {
+            statistics.reset();
+        }
+    }
+}
+
+impl FrameProcessor for Statistics {
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        self.record(frame);
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::ChannelStatistics;
+
+    #[test]
+    fn mean_is_rounded() {
+        let mut statistics = ChannelStatistics::default();
+        assert_eq!(statistics.mean(), None);
+        for sample in [10_u16, 11, 11] {
+            statistics.record(sample);
+        }
+        assert_eq!(statistics.mean(), Some(11));
+        assert_eq!(statistics.span(), Some(1));
+        assert_eq!(statistics.count(), 3);
+    }
+
+    #[test]
+    fn mean_stops_at_saturated_count() {
+        let mut statistics = ChannelStatistics::default();
+        statistics.record(1000);
+        statistics.count = u32::MAX - 1;
+        statistics.sum = u64::from(statistics.count) * 1000;
+        statistics.record(1000);
+        statistics.record(u16::MAX);
+        assert_eq!(statistics.count(), u32::MAX);
+        assert_eq!(statistics.mean(), Some(1000));
+        assert_eq!(statistics.max(), Some(u16::MAX));
+    }
+}
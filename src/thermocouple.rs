@@ -0,0 +1,511 @@
+//! Thermocouple measurement with cold-junction compensation.
+//!
+//! `Thermocouple` pairs a channel reading an amplified thermocouple with a channel measuring
+//! the temperature of the cold junction, typically a thermistor on the terminal block. The
+//! cold-junction temperature is converted to its equivalent thermocouple voltage and added to
+//! the measured voltage, and the sum converted back to the hot-junction temperature.
+//!
+//! Conversions use tables of the ITS-90 reference functions at 10 degC steps with linear
+//! interpolation, so no floating point is needed.
+
+use core::fmt;
+
+use crate::{
+    channel::Channel,
+    chip_definitions::Resolution,
+    lookup::interpolate,
+    sensor::{SensorModel, Validity},
+};
+
+const STEP_CENTI_CELSIUS: i32 = 1000;
+
+/// Thermocouple voltage at each temperature step from `first_celsius`.
+#[derive(Debug)]
+struct EmfTable {
+    first_celsius: i32,
+    microvolts: &'static [i32],
+}
+
+impl EmfTable {
+    fn centi_celsius_at(&self, index: usize) -> Option<i32> {
+        Some(self.first_celsius * 100 + i32::try_from(index).ok()? * STEP_CENTI_CELSIUS)
+    }
+
+    fn microvolts(&self, centi_celsius: i32) -> Option<i32> {
+        let offset = u32::try_from(centi_celsius.checked_sub(self.first_celsius * 100)?).ok()?;
+        let index = usize::try_from(offset / STEP_CENTI_CELSIUS.unsigned_abs()).ok()?;
+        let &lower = self.microvolts.get(index)?;
+        let lower_centi_celsius = self.centi_celsius_at(index)?;
+        if lower_centi_celsius == centi_celsius {
+            return Some(lower);
+        }
+        let &upper = self.microvolts.get(index + 1)?;
+        Some(interpolate(
+            (i64::from(lower_centi_celsius), i64::from(lower)),
+            (
+                i64::from(lower_centi_celsius + STEP_CENTI_CELSIUS),
+                i64::from(upper),
+            ),
+            i64::from(centi_celsius),
+        ))
+    }
+
+    fn centi_celsius(&self, microvolts: i32) -> Option<i32> {
+        let index = self.microvolts.partition_point(|&entry| entry < microvolts);
+        let &upper = self.microvolts.get(index)?;
+        let upper_centi_celsius = self.centi_celsius_at(index)?;
+        if upper == microvolts {
+            return Some(upper_centi_celsius);
+        }
+        let &lower = self.microvolts.get(index.checked_sub(1)?)?;
+        Some(interpolate(
+            (
+                i64::from(lower),
+                i64::from(upper_centi_celsius - STEP_CENTI_CELSIUS),
+            ),
+            (i64::from(upper), i64::from(upper_centi_celsius)),
+            i64::from(microvolts),
+        ))
+    }
+}
+
+/// Type K, -200 degC to 1370 degC.
+#[allow(
+    clippy::decimal_literal_representation,
+    clippy::default_numeric_fallback,
+    reason = "Reference table data, kept in the decimal microvolts of the published tables."
+)]
+const TYPE_K: EmfTable = EmfTable {
+    first_celsius: -200,
+    microvolts: &[
+        -5_891, -5_730, -5_550, -5_354, -5_141, -4_913, -4_669, -4_411, -4_138, -3_852, -3_554,
+        -3_243, -2_920, -2_587, -2_243, -1_889, -1_527, -1_156, -778, -392, 0, 397, 798, 1_203,
+        1_612, 2_023, 2_436, 2_851, 3_267, 3_682, 4_096, 4_509, 4_920, 5_328, 5_735, 6_138, 6_540,
+        6_941, 7_340, 7_739, 8_138, 8_539, 8_940, 9_343, 9_747, 10_153, 10_561, 10_971, 11_382,
+        11_795, 12_209, 12_624, 13_040, 13_457, 13_874, 14_293, 14_713, 15_133, 15_554, 15_975,
+        16_397, 16_820, 17_243, 17_667, 18_091, 18_516, 18_941, 19_366, 19_792, 20_218, 20_644,
+        21_071, 21_497, 21_924, 22_350, 22_776, 23_203, 23_629, 24_055, 24_480, 24_905, 25_330,
+        25_755, 26_179, 26_602, 27_025, 27_447, 27_869, 28_289, 28_710, 29_129, 29_548, 29_965,
+        30_382, 30_798, 31_213, 31_628, 32_041, 32_453, 32_865, 33_275, 33_685, 34_093, 34_501,
+        34_908, 35_313, 35_718, 36_121, 36_524, 36_925, 37_326, 37_725, 38_124, 38_522, 38_918,
+        39_314, 39_708, 40_101, 40_494, 40_885, 41_276, 41_665, 42_053, 42_440, 42_826, 43_211,
+        43_595, 43_978, 44_359, 44_740, 45_119, 45_497, 45_873, 46_249, 46_623, 46_995, 47_367,
+        47_737, 48_105, 48_473, 48_838, 49_202, 49_565, 49_926, 50_286, 50_644, 51_000, 51_355,
+        51_708, 52_060, 52_410, 52_759, 53_106, 53_451, 53_795, 54_138, 54_479, 54_819,
+    ],
+};
+
+/// Type J, -200 degC to 760 degC.
+#[allow(
+    clippy::decimal_literal_representation,
+    clippy::default_numeric_fallback,
+    reason = "Reference table data, kept in the decimal microvolts of the published tables."
+)]
+const TYPE_J: EmfTable = EmfTable {
+    first_celsius: -200,
+    microvolts: &[
+        -7_890, -7_659, -7_403, -7_123, -6_821, -6_500, -6_159, -5_801, -5_426, -5_037, -4_633,
+        -4_215, -3_786, -3_344, -2_893, -2_431, -1_961, -1_482, -995, -501, 0, 507, 1_019, 1_537,
+        2_059, 2_585, 3_116, 3_650, 4_187, 4_726, 5_269, 5_814, 6_360, 6_909, 7_459, 8_010, 8_562,
+        9_115, 9_669, 10_224, 10_779, 11_334, 11_889, 12_445, 13_000, 13_555, 14_110, 14_665,
+        15_219, 15_773, 16_327, 16_881, 17_434, 17_986, 18_538, 19_090, 19_642, 20_194, 20_745,
+        21_297, 21_848, 22_400, 22_952, 23_504, 24_057, 24_610, 25_164, 25_720, 26_276, 26_834,
+        27_393, 27_953, 28_516, 29_080, 29_647, 30_216, 30_788, 31_362, 31_939, 32_519, 33_102,
+        33_689, 34_279, 34_873, 35_470, 36_071, 36_675, 37_284, 37_896, 38_512, 39_132, 39_755,
+        40_382, 41_012, 41_645, 42_281, 42_919,
+    ],
+};
+
+/// Type T, -200 degC to 400 degC.
+#[allow(
+    clippy::decimal_literal_representation,
+    clippy::default_numeric_fallback,
+    reason = "Reference table data, kept in the decimal microvolts of the published tables."
+)]
+const TYPE_T: EmfTable = EmfTable {
+    first_celsius: -200,
+    microvolts: &[
+        -5_603, -5_439, -5_261, -5_070, -4_865, -4_648, -4_419, -4_177, -3_923, -3_657, -3_379,
+        -3_089, -2_788, -2_476, -2_153, -1_819, -1_475, -1_121, -757, -383, 0, 391, 790, 1_196,
+        1_612, 2_036, 2_468, 2_909, 3_358, 3_814, 4_279, 4_750, 5_228, 5_714, 6_206, 6_704, 7_209,
+        7_720, 8_237, 8_759, 9_288, 9_822, 10_362, 10_907, 11_458, 12_013, 12_574, 13_139, 13_709,
+        14_283, 14_862, 15_445, 16_032, 16_624, 17_219, 17_819, 18_422, 19_030, 19_641, 20_255,
+        20_872,
+    ],
+};
+
+/// Thermocouple types with built-in conversion tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThermocoupleType {
+    /// Chromel-alumel, -200 degC to 1370 degC.
+    K,
+    /// Iron-constantan, -200 degC to 760 degC.
+    J,
+    /// Copper-constantan, -200 degC to 400 degC.
+    T,
+}
+
+impl ThermocoupleType {
+    fn table(self) -> &'static EmfTable {
+        match self {
+            ThermocoupleType::K => &TYPE_K,
+            ThermocoupleType::J => &TYPE_J,
+            ThermocoupleType::T => &TYPE_T,
+        }
+    }
+
+    /// Thermocouple voltage for a junction temperature, referenced to 0 degC, or `None` outside
+    /// the type's range.
+    #[must_use]
+    pub fn microvolts(self, centi_celsius: i32) -> Option<i32> {
+        self.table().microvolts(centi_celsius)
+    }
+
+    /// Junction temperature for a thermocouple voltage referenced to 0 degC, or `None` outside
+    /// the type's range.
+    #[must_use]
+    pub fn centi_celsius(self, microvolts: i32) -> Option<i32> {
+        self.table().centi_celsius(microvolts)
+    }
+}
+
+/// The instrumentation amplifier between the thermocouple and the ADC input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Amplifier {
+    /// Gain in thousandths, `101_000` for a gain of 101.
+    pub gain_milli: u32,
+    /// Output voltage with no input, set by the amplifier's reference pin.
+    pub offset_microvolts: i32,
+    pub avdd_microvolts: u32,
+    pub resolution: Resolution,
+}
+
+impl Amplifier {
+    /// Voltage at the amplifier input for a result.
+    #[must_use]
+    pub fn input_microvolts(&self, code: u16) -> i32 {
+        let output = i64::from(code) * i64::from(self.avdd_microvolts)
+            / i64::from(self.resolution.full_scale());
+        let input =
+            (output - i64::from(self.offset_microvolts)) * 1000 / i64::from(self.gain_milli.max(1));
+        i32::try_from(input).unwrap_or(if input < 0 { i32::MIN } else { i32::MAX })
+    }
+}
+
+/// Why a hot-junction temperature could not be determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThermocoupleFault {
+    /// The amplifier output is at full scale, as a burnout resistor drives it when the
+    /// thermocouple is open.
+    Open,
+    /// The cold-junction reading was not valid.
+    ColdJunction(Validity),
+    /// A temperature is outside the range of the thermocouple type.
+    OutOfRange,
+}
+
+/// A thermocouple channel and its cold-junction channel.
+///
+/// With the `serde` feature the type, amplifier and channels are serialized. The
+/// cold-junction model is a trait object, so it is left out and the thermocouple is not
+/// deserialized.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Thermocouple<'a> {
+    kind: ThermocoupleType,
+    amplifier: Amplifier,
+    hot_junction: Channel,
+    cold_junction: Channel,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cold_junction_model: &'a dyn SensorModel,
+}
+
+impl<'a> Thermocouple<'a> {
+    /// Creates a measurement of `hot_junction`, compensated with the temperature read on
+    /// `cold_junction`. `cold_junction_model` must give centi-degrees Celsius, as
+    /// `ntc::Ntc` does.
+    #[must_use]
+    pub fn new(
+        kind: ThermocoupleType,
+        amplifier: Amplifier,
+        hot_junction: Channel,
+        cold_junction: Channel,
+        cold_junction_model: &'a dyn SensorModel,
+    ) -> Self {
+        Thermocouple {
+            kind,
+            amplifier,
+            hot_junction,
+            cold_junction,
+            cold_junction_model,
+        }
+    }
+
+    /// Hot-junction temperature in centi-degrees Celsius from a result of each channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ThermocoupleFault::Open` if the amplifier is saturated,
+    /// `ThermocoupleFault::ColdJunction` if the cold-junction reading is not valid, and
+    /// `ThermocoupleFault::OutOfRange` for temperatures outside the thermocouple type's range.
+    pub fn compensate(
+        &self,
+        thermocouple_code: u16,
+        cold_junction_code: u16,
+    ) -> Result<i32, ThermocoupleFault> {
+        if u32::from(thermocouple_code) + 1 >= self.amplifier.resolution.full_scale() {
+            return Err(ThermocoupleFault::Open);
+        }
+        let cold_junction = self.cold_junction_model.convert(cold_junction_code);
+        if !cold_junction.is_valid() {
+            return Err(ThermocoupleFault::ColdJunction(cold_junction.validity));
+        }
+        let cold_junction_microvolts = self
+            .kind
+            .microvolts(cold_junction.value)
+            .ok_or(ThermocoupleFault::OutOfRange)?;
+        let measured_microvolts = self.amplifier.input_microvolts(thermocouple_code);
+        self.kind
+            .centi_celsius(measured_microvolts.saturating_add(cold_junction_microvolts))
+            .ok_or(ThermocoupleFault::OutOfRange)
+    }
+
+    /// Hot-junction temperature in centi-degrees Celsius from a frame of results indexed by
+    /// channel.
+    ///
+    /// # Errors
+    ///
+    /// As for `compensate()`.
+    pub fn measure(&self, frame: &[u16; 8]) -> Result<i32, ThermocoupleFault> {
+        let code = |channel: Channel| frame.get(channel.index()).copied().unwrap_or(0);
+        self.compensate(code(self.hot_junction), code(self.cold_junction))
+    }
+}
+
+impl fmt::Debug for Thermocouple<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Thermocouple")
+            .field("kind", &self.kind)
+            .field("amplifier", &self.amplifier)
+            .field("hot_junction", &self.hot_junction)
+            .field("cold_junction", &self.cold_junction)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt")]
+#[allow(
+    clippy::missing_trait_methods,
+    reason = "The provided methods of defmt::Format are internal to defmt."
+)]
+impl defmt::Format for Thermocouple<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "Thermocouple {{ kind: {}, amplifier: {}, hot_junction: {}, cold_junction: {}, .. }}",
+            self.kind,
+            self.amplifier,
+            self.hot_junction,
+            self.cold_junction
+        );
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{Amplifier, Thermocouple, ThermocoupleFault, ThermocoupleType};
+    use crate::{
+        channel::Channel,
+        chip_definitions::Resolution,
+        sensor::{Reading, SensorModel, Validity},
+    };
+
+    /// Points from the NIST ITS-90 thermocouple tables, in degC and microvolts.
+    const NIST_K: [(i32, i32); 7] = [
+        (-200, -5_891),
+        (-100, -3_554),
+        (100, 4_096),
+        (200, 8_138),
+        (500, 20_644),
+        (1000, 41_276),
+        (1370, 54_819),
+    ];
+    const NIST_J: [(i32, i32); 6] = [
+        (-200, -7_890),
+        (-100, -4_633),
+        (100, 5_269),
+        (200, 10_779),
+        (500, 27_393),
+        (760, 42_919),
+    ];
+    const NIST_T: [(i32, i32); 5] = [
+        (-200, -5_603),
+        (-100, -3_379),
+        (100, 4_279),
+        (200, 9_288),
+        (400, 20_872),
+    ];
+
+    /// Gain of 50 above a 1.024 V reference, so each 16-bit code is 1.25 uV at the input and
+    /// the input range is -20.48 mV to 61.44 mV.
+    const AMPLIFIER: Amplifier = Amplifier {
+        gain_milli: 50_000,
+        offset_microvolts: 1_024_000,
+        avdd_microvolts: 4_096_000,
+        resolution: Resolution::Bits16,
+    };
+
+    /// The code for no input, the amplifier's 1.024 V reference.
+    const ZERO_CODE: i32 = 1 << 14;
+
+    /// The code for a thermocouple voltage, rounded to the nearest code.
+    fn code_for(microvolts: i32) -> u16 {
+        u16::try_from((microvolts * 4 + 2) / 5 + ZERO_CODE).unwrap_or(u16::MAX)
+    }
+
+    /// A cold-junction sensor that always gives the same reading.
+    struct ColdJunction(Reading);
+
+    impl SensorModel for ColdJunction {
+        fn convert(&self, _code: u16) -> Reading {
+            self.0
+        }
+    }
+
+    fn assert_close(actual: Option<i32>, expected: i32, tolerance: i32) {
+        let value = actual.unwrap_or(i32::MIN);
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{actual:?} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn tables_match_nist_reference_points() {
+        for (kind, points) in [
+            (ThermocoupleType::K, &NIST_K[..]),
+            (ThermocoupleType::J, &NIST_J[..]),
+            (ThermocoupleType::T, &NIST_T[..]),
+        ] {
+            assert_eq!(kind.microvolts(0), Some(0_i32));
+            for &(celsius, microvolts) in points {
+                assert_eq!(kind.microvolts(celsius * 100), Some(microvolts), "{kind:?}");
+                assert_eq!(
+                    kind.centi_celsius(microvolts),
+                    Some(celsius * 100_i32),
+                    "{kind:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn values_between_steps_are_close_to_nist() {
+        // NIST gives 1.000 mV, 1.277 mV and 0.992 mV at 25 degC
+        assert_close(ThermocoupleType::K.microvolts(2500), 1_000, 1);
+        assert_close(ThermocoupleType::J.microvolts(2500), 1_277, 1);
+        assert_close(ThermocoupleType::T.microvolts(2500), 992, 1);
+        assert_close(ThermocoupleType::K.centi_celsius(1_000), 2500, 1);
+        // 37.0 degC and 300.0 degC on type K
+        assert_close(ThermocoupleType::K.centi_celsius(1_489), 3700, 5);
+        assert_close(ThermocoupleType::K.centi_celsius(12_209), 30_000, 0);
+        assert_close(ThermocoupleType::K.microvolts(-15_000), -4_913, 0);
+    }
+
+    #[test]
+    fn temperatures_outside_each_type_are_rejected() {
+        assert_eq!(ThermocoupleType::K.microvolts(-20_001), None);
+        assert_eq!(ThermocoupleType::K.microvolts(137_001), None);
+        assert_eq!(ThermocoupleType::J.microvolts(76_001), None);
+        assert_eq!(ThermocoupleType::T.microvolts(40_010), None);
+        assert_eq!(ThermocoupleType::K.centi_celsius(-5_892), None);
+        assert_eq!(ThermocoupleType::J.centi_celsius(42_920), None);
+        assert_eq!(ThermocoupleType::T.centi_celsius(20_873), None);
+    }
+
+    #[test]
+    fn cold_junction_voltage_is_added_to_the_measurement() {
+        let cold_junction = ColdJunction(Reading::valid(2500));
+        for (kind, &(celsius, microvolts)) in [
+            (ThermocoupleType::K, &NIST_K[3]),
+            (ThermocoupleType::J, &NIST_J[4]),
+            (ThermocoupleType::T, &NIST_T[1]),
+        ] {
+            let thermocouple = Thermocouple::new(
+                kind,
+                AMPLIFIER,
+                Channel::Channel0,
+                Channel::Channel1,
+                &cold_junction,
+            );
+            let cold_junction_microvolts = kind.microvolts(2500).unwrap_or_default();
+            let code = code_for(microvolts - cold_junction_microvolts);
+            // Within a code of quantisation, 0.05 degC at worst
+            assert_close(thermocouple.compensate(code, 0).ok(), celsius * 100, 5);
+        }
+    }
+
+    #[test]
+    fn cold_junction_below_zero_is_compensated() {
+        let cold_junction = ColdJunction(Reading::valid(-1000));
+        let thermocouple = Thermocouple::new(
+            ThermocoupleType::K,
+            AMPLIFIER,
+            Channel::Channel6,
+            Channel::Channel2,
+            &cold_junction,
+        );
+        // -10 degC is -392 uV on type K, so 100 degC measures 4.488 mV
+        let mut frame = [0; 8];
+        frame[Channel::Channel6.index()] = code_for(4_096 + 392);
+        assert_close(thermocouple.measure(&frame).ok(), 10_000, 5);
+        // With the junctions at the same temperature nothing is measured
+        assert_close(thermocouple.compensate(code_for(0), 0).ok(), -1000, 5);
+    }
+
+    #[test]
+    fn faults_are_reported() {
+        let cold_junction = ColdJunction(Reading::valid(2500));
+        let thermocouple = |kind, model| {
+            Thermocouple::new(kind, AMPLIFIER, Channel::Channel0, Channel::Channel1, model)
+        };
+        let type_k = thermocouple(ThermocoupleType::K, &cold_junction);
+        assert_eq!(type_k.compensate(u16::MAX, 0), Err(ThermocoupleFault::Open));
+        assert_eq!(
+            type_k.compensate(code_for(54_819), 0),
+            Err(ThermocoupleFault::OutOfRange)
+        );
+        assert_eq!(
+            type_k.compensate(code_for(-7_000), 0),
+            Err(ThermocoupleFault::OutOfRange)
+        );
+        let type_t = thermocouple(ThermocoupleType::T, &cold_junction);
+        assert_eq!(
+            type_t.compensate(code_for(20_000), 0),
+            Err(ThermocoupleFault::OutOfRange)
+        );
+        let hot_cold_junction = ColdJunction(Reading::valid(50_000));
+        assert_eq!(
+            thermocouple(ThermocoupleType::T, &hot_cold_junction).compensate(code_for(0), 0),
+            Err(ThermocoupleFault::OutOfRange)
+        );
+        let open_cold_junction = ColdJunction(Reading {
+            value: 0,
+            validity: Validity::Fault,
+        });
+        assert_eq!(
+            thermocouple(ThermocoupleType::K, &open_cold_junction).compensate(code_for(0), 0),
+            Err(ThermocoupleFault::ColdJunction(Validity::Fault))
+        );
+    }
+}
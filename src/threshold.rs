@@ -0,0 +1,357 @@
+//! Software window comparators for acquired frames.
+//!
+//! The TLA2528 has no hardware event comparators, so `ThresholdMonitor` checks each frame
+//! against per-channel low and high limits. A channel trips when its value leaves the window
+//! and stays in alarm until it returns inside the window by at least the hysteresis, so a
+//! signal near a limit does not raise a stream of events.
+
+use crate::{
+    channel::{Channel, ChannelFlags},
+    filter::FrameProcessor,
+};
+
+/// Limits for one channel. Values above `high` or below `low` trip the channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Threshold {
+    pub low: u16,
+    pub high: u16,
+    /// Distance back inside the window a value must reach before an alarm clears.
+    pub hysteresis: u16,
+}
+
+/// Alarm state of a monitored channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThresholdState {
+    #[default]
+    Normal,
+    AboveHigh,
+    BelowLow,
+}
+
+/// Number of times a channel has tripped each limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventCounts {
+    pub high: u32,
+    pub low: u32,
+}
+
+/// The outcome of evaluating one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThresholdEvents {
+    /// Channels that entered an alarm state, or switched between limits, on this frame.
+    pub tripped: ChannelFlags,
+    /// Channels whose alarm cleared on this frame.
+    pub cleared: ChannelFlags,
+    /// Channels in an alarm state after this frame.
+    pub active: ChannelFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ChannelMonitor {
+    threshold: Option<Threshold>,
+    state: ThresholdState,
+    counts: EventCounts,
+}
+
+impl ChannelMonitor {
+    /// Updates the state with a new value and returns the new state if it changed.
+    fn evaluate(&mut self, value: u16) -> Option<ThresholdState> {
+        let threshold = self.threshold?;
+        let next = if value > threshold.high {
+            ThresholdState::AboveHigh
+        } else if value < threshold.low {
+            ThresholdState::BelowLow
+        } else {
+            match self.state {
+                ThresholdState::AboveHigh
+                    if value > threshold.high.saturating_sub(threshold.hysteresis) =>
+                {
+                    ThresholdState::AboveHigh
+                }
+                ThresholdState::BelowLow
+                    if value < threshold.low.saturating_add(threshold.hysteresis) =>
+                {
+                    ThresholdState::BelowLow
+                }
+                ThresholdState::Normal | ThresholdState::AboveHigh | ThresholdState::BelowLow => {
+                    ThresholdState::Normal
+                }
+            }
+        };
+        if next == self.state {
+            return None;
+        }
+        match next {
+            ThresholdState::AboveHigh => self.counts.high = self.counts.high.saturating_add(1),
+            ThresholdState::BelowLow => self.counts.low = self.counts.low.saturating_add(1),
+            ThresholdState::Normal => {}
+        }
+        self.state = next;
+        Some(next)
+    }
+}
+
+/// Per-channel window comparators with hysteresis and event counts.
+///
+/// As a `FrameProcessor` the monitor evaluates every frame it sees and accumulates the tripped
+/// channels until `take_tripped()` is called; frames are not modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThresholdMonitor {
+    channels: [ChannelMonitor; 8],
+    pending: ChannelFlags,
+}
+
+impl ThresholdMonitor {
+    /// Creates a monitor with no channels monitored.
+    #[must_use]
+    pub fn new() -> Self {
+        ThresholdMonitor::default()
+    }
+
+    /// Sets the limits of a channel and returns it to the normal state.
+    pub fn set_threshold(&mut self, channel: Channel, threshold: Threshold) {
+        if let Some(monitor) = self.channels.get_mut(channel.index()) {
+            monitor.threshold = Some(threshold);
+            monitor.state = ThresholdState::Normal;
+        }
+    }
+
+    /// Stops monitoring a channel.
+    pub fn clear_threshold(&mut self, channel: Channel) {
+        if let Some(monitor) = self.channels.get_mut(channel.index()) {
+            monitor.threshold = None;
+            monitor.state = ThresholdState::Normal;
+        }
+    }
+
+    #[must_use]
+    pub fn threshold(&self, channel: Channel) -> Option<Threshold> {
+        self.channels
+            .get(channel.index())
+            .and_then(|monitor| monitor.threshold)
+    }
+
+    #[must_use]
+    pub fn state(&self, channel: Channel) -> ThresholdState {
+        self.channels
+            .get(channel.index())
+            .map_or(ThresholdState::Normal, |monitor| monitor.state)
+    }
+
+    #[must_use]
+    pub fn event_counts(&self, channel: Channel) -> EventCounts {
+        self.channels
+            .get(channel.index())
+            .map_or(EventCounts::default(), |monitor| monitor.counts)
+    }
+
+    /// Channels currently in an alarm state.
+    #[must_use]
+    pub fn active(&self) -> ChannelFlags {
+        Channel::ALL
+            .into_iter()
+            .filter(|&channel| self.state(channel) != ThresholdState::Normal)
+            .map(ChannelFlags::from)
+            .collect()
+    }
+
+    /// Evaluates a frame of results indexed by channel.
+    pub fn evaluate(&mut self, frame: &[u16; 8]) -> ThresholdEvents {
+        let mut events = ThresholdEvents::default();
+        for ((channel, monitor), &value) in
+            Channel::ALL.into_iter().zip(&mut self.channels).zip(frame)
+        {
+            match monitor.evaluate(value) {
+                Some(ThresholdState::Normal) => events.cleared |= ChannelFlags::from(channel),
+                Some(ThresholdState::AboveHigh | ThresholdState::BelowLow) => {
+                    events.tripped |= ChannelFlags::from(channel);
+                }
+                None => {}
+            }
+        }
+        events.active = self.active();
+        self.pending |= events.tripped;
+        events
+    }
+
+    /// Returns the channels that tripped since the last call, across all frames evaluated.
+    pub fn take_tripped(&mut self) -> ChannelFlags {
+        core::mem::take(&mut self.pending)
+    }
+
+    /// Returns every channel to the normal state and zeroes the event counts, keeping the limits.
+    pub fn reset(&mut self) {
+        for monitor in &mut self.channels {
+            monitor.state = ThresholdState::Normal;
+            monitor.counts = EventCounts::default();
+        }
+        self.pending = ChannelFlags::empty();
+    }
+}
+
+impl FrameProcessor for ThresholdMonitor {
+    fn process_frame(&mut self, frame: &mut [u16; 8]) {
+        self.evaluate(frame);
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use super::{EventCounts, Threshold, ThresholdEvents, ThresholdMonitor, ThresholdState};
+    use crate::{
+        channel::{Channel, ChannelFlags},
+        filter::FrameProcessor,
+    };
+
+    const LIMITS: Threshold = Threshold {
+        low: 1000,
+        high: 3000,
+        hysteresis: 100,
+    };
+
+    fn monitor() -> ThresholdMonitor {
+        let mut monitor = ThresholdMonitor::new();
+        monitor.set_threshold(Channel::Channel2, LIMITS);
+        monitor
+    }
+
+    /// Evaluates a frame with `value` on the monitored channel and mid-scale elsewhere.
+    fn step(monitor: &mut ThresholdMonitor, value: u16) -> ThresholdEvents {
+        let mut frame = [2000; 8];
+        frame[Channel::Channel2.index()] = value;
+        monitor.evaluate(&frame)
+    }
+
+    fn tripped() -> ThresholdEvents {
+        ThresholdEvents {
+            tripped: ChannelFlags::CHANNEL2,
+            cleared: ChannelFlags::empty(),
+            active: ChannelFlags::CHANNEL2,
+        }
+    }
+
+    fn cleared() -> ThresholdEvents {
+        ThresholdEvents {
+            tripped: ChannelFlags::empty(),
+            cleared: ChannelFlags::CHANNEL2,
+            active: ChannelFlags::empty(),
+        }
+    }
+
+    fn held() -> ThresholdEvents {
+        ThresholdEvents {
+            active: ChannelFlags::CHANNEL2,
+            ..ThresholdEvents::default()
+        }
+    }
+
+    #[test]
+    fn high_alarm_holds_inside_the_hysteresis_band() {
+        let mut monitor = monitor();
+        assert_eq!(step(&mut monitor, 3000), ThresholdEvents::default());
+        assert_eq!(step(&mut monitor, 3001), tripped());
+        // Dropping back inside the window but not past the band, then crossing again, does
+        // not raise a second event
+        assert_eq!(step(&mut monitor, 2950), held());
+        assert_eq!(step(&mut monitor, 3050), held());
+        assert_eq!(step(&mut monitor, 2901), held());
+        assert_eq!(monitor.state(Channel::Channel2), ThresholdState::AboveHigh);
+        assert_eq!(step(&mut monitor, 2900), cleared());
+        assert_eq!(step(&mut monitor, 3001), tripped());
+        assert_eq!(
+            monitor.event_counts(Channel::Channel2),
+            EventCounts { high: 2, low: 0 }
+        );
+    }
+
+    #[test]
+    fn low_alarm_holds_inside_the_hysteresis_band() {
+        let mut monitor = monitor();
+        assert_eq!(step(&mut monitor, 1000), ThresholdEvents::default());
+        assert_eq!(step(&mut monitor, 999), tripped());
+        assert_eq!(step(&mut monitor, 1050), held());
+        assert_eq!(step(&mut monitor, 950), held());
+        assert_eq!(step(&mut monitor, 1099), held());
+        assert_eq!(monitor.state(Channel::Channel2), ThresholdState::BelowLow);
+        assert_eq!(step(&mut monitor, 1100), cleared());
+        assert_eq!(
+            monitor.event_counts(Channel::Channel2),
+            EventCounts { high: 0, low: 1 }
+        );
+    }
+
+    #[test]
+    fn switching_limits_counts_as_a_new_event() {
+        let mut monitor = monitor();
+        assert_eq!(step(&mut monitor, 4000), tripped());
+        assert_eq!(step(&mut monitor, 10), tripped());
+        assert_eq!(monitor.state(Channel::Channel2), ThresholdState::BelowLow);
+        assert_eq!(
+            monitor.event_counts(Channel::Channel2),
+            EventCounts { high: 1, low: 1 }
+        );
+    }
+
+    #[test]
+    fn unmonitored_channels_never_trip() {
+        let mut monitor = monitor();
+        let events = monitor.evaluate(&[0, u16::MAX, 2000, 0, u16::MAX, 0, 0, 0]);
+        assert_eq!(events, ThresholdEvents::default());
+        monitor.clear_threshold(Channel::Channel2);
+        assert_eq!(step(&mut monitor, 4000), ThresholdEvents::default());
+        assert_eq!(monitor.threshold(Channel::Channel2), None);
+    }
+
+    #[test]
+    fn take_tripped_collects_events_across_frames_and_clears_them() {
+        let mut monitor = monitor();
+        monitor.set_threshold(Channel::Channel7, LIMITS);
+        let mut frame = [2000; 8];
+        frame[Channel::Channel2.index()] = 4000;
+        monitor.process_frame(&mut frame);
+        frame[Channel::Channel2.index()] = 2000;
+        frame[Channel::Channel7.index()] = 0;
+        monitor.process_frame(&mut frame);
+        // Channel 2 has cleared again, but its trip is still reported
+        assert_eq!(
+            monitor.take_tripped(),
+            ChannelFlags::CHANNEL2 | ChannelFlags::CHANNEL7
+        );
+        assert_eq!(monitor.active(), ChannelFlags::CHANNEL7);
+        assert_eq!(monitor.take_tripped(), ChannelFlags::empty());
+        // A channel that stays in alarm does not trip again
+        monitor.process_frame(&mut frame);
+        assert_eq!(monitor.take_tripped(), ChannelFlags::empty());
+    }
+
+    #[test]
+    fn reset_zeroes_counts_and_keeps_limits() {
+        let mut monitor = monitor();
+        step(&mut monitor, 4000);
+        monitor.reset();
+        assert_eq!(monitor.state(Channel::Channel2), ThresholdState::Normal);
+        assert_eq!(
+            monitor.event_counts(Channel::Channel2),
+            EventCounts::default()
+        );
+        assert_eq!(monitor.take_tripped(), ChannelFlags::empty());
+        assert_eq!(monitor.threshold(Channel::Channel2), Some(LIMITS));
+        assert_eq!(step(&mut monitor, 4000), tripped());
+    }
+}
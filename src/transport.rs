@@ -0,0 +1,310 @@
+//! Bus access underneath the register protocol.
+//!
+//! The TLA2528 and its siblings share one opcode protocol: single register reads and writes,
+//! set-bit and clear-bit commands, and conversion frames of two or three bytes each. `Transport`
+//! is that protocol as seen by the driver, so `Tla2528` can run over any bus, a mock or a bridge
+//! chip. `I2cTransport` carries it over `embedded_hal::i2c::I2c` for the TLA2528, and
+//! `SpiTransport` over `embedded_hal::spi::SpiDevice` for the TLA2518 and ADS7038, which share
+//! its register map.
+//!
+//! Transports return their raw bus errors; the driver adds the register or operation that
+//! failed when it wraps them in `error::Error`.
+
+use embedded_hal::{
+    i2c::{I2c, SevenBitAddress},
+    spi::SpiDevice,
+};
+
+use crate::chip_definitions::{OpCode, RegisterAddress};
+
+/// Register and data access to a device speaking the TLA2528 opcode protocol.
+pub trait Transport {
+    type Error;
+
+    /// Reads one register.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus errors.
+    fn register_read(&mut self, register: RegisterAddress) -> Result<u8, Self::Error>;
+
+    /// Writes one register.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus errors.
+    fn register_write(&mut self, register: RegisterAddress, value: u8) -> Result<(), Self::Error>;
+
+    /// Sets the bits of `mask` in a register, leaving the others unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus errors.
+    fn set_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error>;
+
+    /// Clears the bits of `mask` in a register, leaving the others unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus errors.
+    fn clear_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error>;
+
+    /// Fills `buffer` with conversion data, one frame of `frame_len` bytes after another.
+    ///
+    /// # Errors
+    ///
+    /// Passes out bus errors.
+    fn read_frames(&mut self, buffer: &mut [u8], frame_len: usize) -> Result<(), Self::Error>;
+}
+
+/// The opcode protocol over I2C, addressing one device on the bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct I2cTransport<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cTransport<I2C>
+where
+    I2C: I2c<SevenBitAddress>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cTransport { i2c, address }
+    }
+
+    /// The 7-bit address of the device.
+    #[must_use]
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+}
+
+impl<I2C> Transport for I2cTransport<I2C>
+where
+    I2C: I2c<SevenBitAddress>,
+{
+    type Error = I2C::Error;
+
+    fn register_read(&mut self, register: RegisterAddress) -> Result<u8, Self::Error> {
+        let mut incoming = [0_u8; 1];
+        self.i2c.write_read(
+            self.address,
+            &[OpCode::SingleRegisterRead.value(), register.value()],
+            &mut incoming,
+        )?;
+        Ok(incoming[0])
+    }
+
+    fn register_write(&mut self, register: RegisterAddress, value: u8) -> Result<(), Self::Error> {
+        self.i2c.write(
+            self.address,
+            &[OpCode::SingleRegisterWrite.value(), register.value(), value],
+        )
+    }
+
+    fn set_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error> {
+        self.i2c.write(
+            self.address,
+            &[OpCode::SetBit.value(), register.value(), mask],
+        )
+    }
+
+    fn clear_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error> {
+        self.i2c.write(
+            self.address,
+            &[OpCode::ClearBit.value(), register.value(), mask],
+        )
+    }
+
+    fn read_frames(&mut self, buffer: &mut [u8], _frame_len: usize) -> Result<(), Self::Error> {
+        // Frames follow each other within one read
+        self.i2c.read(self.address, buffer)
+    }
+}
+
+/// The opcode protocol over SPI, with the device on its own chip select.
+///
+/// Every frame is `[opcode, address, data]`. Register data is returned in the first byte of the
+/// frame following a read command, and conversion data is clocked out with no-operation frames,
+/// one 2- or 3-byte frame per conversion. Each frame is a separate `SpiDevice` transaction, as the
+/// device starts a conversion when chip select is released.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpiTransport<SPI> {
+    spi: SPI,
+}
+
+impl<SPI> SpiTransport<SPI>
+where
+    SPI: SpiDevice,
+{
+    pub fn new(spi: SPI) -> Self {
+        SpiTransport { spi }
+    }
+
+    fn command(
+        &mut self,
+        opcode: OpCode,
+        register: RegisterAddress,
+        data: u8,
+    ) -> Result<(), SPI::Error> {
+        self.spi.write(&[opcode.value(), register.value(), data])
+    }
+}
+
+impl<SPI> Transport for SpiTransport<SPI>
+where
+    SPI: SpiDevice,
+{
+    type Error = SPI::Error;
+
+    fn register_read(&mut self, register: RegisterAddress) -> Result<u8, Self::Error> {
+        self.command(OpCode::SingleRegisterRead, register, 0)?;
+        let mut incoming = [0_u8; 3];
+        self.spi.transfer_in_place(&mut incoming)?;
+        Ok(incoming[0])
+    }
+
+    fn register_write(&mut self, register: RegisterAddress, value: u8) -> Result<(), Self::Error> {
+        self.command(OpCode::SingleRegisterWrite, register, value)
+    }
+
+    fn set_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error> {
+        self.command(OpCode::SetBit, register, mask)
+    }
+
+    fn clear_bits(&mut self, register: RegisterAddress, mask: u8) -> Result<(), Self::Error> {
+        self.command(OpCode::ClearBit, register, mask)
+    }
+
+    fn read_frames(&mut self, buffer: &mut [u8], frame_len: usize) -> Result<(), Self::Error> {
+        for frame in buffer.chunks_mut(frame_len) {
+            // All-zero frames are no-operation commands
+            frame.fill(0);
+            self.spi.transfer_in_place(frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::missing_assert_message,
+    reason = "Test failures are reported by the assertions themselves."
+)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+    use super::{SpiTransport, Transport};
+    use crate::{
+        channel::Channel,
+        chip_definitions::{Oversampling, RegisterAddress},
+        Tla2518,
+    };
+
+    /// Records the bytes sent in each transaction and answers transfers from `responses`.
+    #[derive(Debug, Default)]
+    struct MockSpi {
+        sent: Vec<Vec<u8>>,
+        responses: Vec<Vec<u8>>,
+    }
+
+    impl ErrorType for MockSpi {
+        type Error = Infallible;
+    }
+
+    #[allow(
+        clippy::missing_trait_methods,
+        reason = "The provided SpiDevice methods all go through the recorded transaction."
+    )]
+    impl SpiDevice for MockSpi {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            let mut sent = Vec::new();
+            for operation in operations.iter_mut() {
+                match *operation {
+                    Operation::Write(bytes) => sent.extend_from_slice(bytes),
+                    Operation::TransferInPlace(ref mut buffer) => {
+                        sent.extend_from_slice(buffer);
+                        let response = if self.responses.is_empty() {
+                            Vec::new()
+                        } else {
+                            self.responses.remove(0)
+                        };
+                        for (byte, value) in buffer.iter_mut().zip(response) {
+                            *byte = value;
+                        }
+                    }
+                    Operation::Read(_) | Operation::Transfer(_, _) | Operation::DelayNs(_) => {}
+                }
+            }
+            self.sent.push(sent);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn commands_are_one_frame_each() {
+        let mut transport = SpiTransport::new(MockSpi::default());
+        transport
+            .register_write(RegisterAddress::OsrConfig, 0x03)
+            .unwrap();
+        transport
+            .set_bits(RegisterAddress::GpOutValue, 0x04)
+            .unwrap();
+        transport
+            .clear_bits(RegisterAddress::GpOutValue, 0x04)
+            .unwrap();
+        let osr = RegisterAddress::OsrConfig.value();
+        let output = RegisterAddress::GpOutValue.value();
+        assert_eq!(
+            transport.spi.sent,
+            [
+                vec![0x08_u8, osr, 0x03],
+                vec![0x18_u8, output, 0x04],
+                vec![0x20_u8, output, 0x04],
+            ]
+        );
+    }
+
+    #[test]
+    fn register_read_takes_data_from_the_following_frame() {
+        let mut transport = SpiTransport::new(MockSpi {
+            responses: vec![vec![0x5A_u8, 0xFF, 0xFF]],
+            ..MockSpi::default()
+        });
+        let value = transport.register_read(RegisterAddress::GpInValue).unwrap();
+        assert_eq!(value, 0x5A);
+        assert_eq!(
+            transport.spi.sent,
+            [
+                vec![0x10_u8, RegisterAddress::GpInValue.value(), 0x00],
+                vec![0x00_u8; 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn conversion_frames_follow_oversampling() {
+        let mut spi = MockSpi {
+            // 12-bit result 0x7A0 from channel 3, then a 16-bit result 0x1234 from channel 3
+            responses: vec![vec![0x7A_u8, 0x03], vec![0x12_u8, 0x34, 0x30]],
+            ..MockSpi::default()
+        };
+        let mut adc = Tla2518::new_spi(&mut spi);
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x7A0);
+        adc.set_oversampling_ratio(Oversampling::Ratio2).unwrap();
+        assert_eq!(adc.acquire_channel_data(Channel::Channel3).unwrap(), 0x1234);
+
+        let frames: Vec<usize> = spi
+            .sent
+            .iter()
+            .filter(|frame| frame.iter().all(|&byte| byte == 0))
+            .map(Vec::len)
+            .collect();
+        assert_eq!(frames, [2, 3]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Virtual channels computed from pairs of physical channels.
+//!
+//! The TLA2528 only has single-ended inputs. A `VirtualChannel` combines two of them into a
+//! pseudo-differential difference or a ratio against a reference channel.
+//! `Tla2528::acquire_virtual()` samples only the channels involved, in one auto-sequence
+//! frame, so the two halves of each pair are converted back to back. The sequencer runs in
+//! ascending channel order, so pairs on adjacent channels have the least skew.
+
+use crate::channel::{Channel, ChannelFlags};
+
+/// A value computed from two physical channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VirtualChannel {
+    /// `positive - negative` in codes.
+    Difference {
+        positive: Channel,
+        negative: Channel,
+    },
+    /// `signal / reference` in parts per million.
+    Ratio { signal: Channel, reference: Channel },
+}
+
+impl VirtualChannel {
+    /// The physical channels the value is computed from.
+    #[must_use]
+    pub fn channels(self) -> ChannelFlags {
+        match self {
+            VirtualChannel::Difference { positive, negative } => {
+                ChannelFlags::from(positive) | ChannelFlags::from(negative)
+            }
+            VirtualChannel::Ratio { signal, reference } => {
+                ChannelFlags::from(signal) | ChannelFlags::from(reference)
+            }
+        }
+    }
+
+    /// Computes the value from a frame of results indexed by channel. Returns `None` if a
+    /// channel was not sampled or a ratio's reference is zero.
+    #[must_use]
+    pub fn evaluate(self, frame: &[Option<u16>; 8]) -> Option<i32> {
+        let sample = |channel: Channel| frame.get(channel.index()).copied().flatten();
+        match self {
+            VirtualChannel::Difference { positive, negative } => {
+                Some(i32::from(sample(positive)?) - i32::from(sample(negative)?))
+            }
+            VirtualChannel::Ratio { signal, reference } => {
+                let divisor = i64::from(sample(reference)?);
+                if divisor == 0 {
+                    return None;
+                }
+                i32::try_from(i64::from(sample(signal)?) * 1_000_000 / divisor).ok()
+            }
+        }
+    }
+}
+
+/// Physical and virtual results from one auto-sequence frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VirtualFrame<const N: usize> {
+    /// Results of the sampled physical channels, indexed by channel.
+    pub physical: [Option<u16>; 8],
+    /// Values of the virtual channels, in the order they were requested.
+    pub virtual_values: [Option<i32>; N],
+}